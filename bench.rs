@@ -0,0 +1,349 @@
+// See LICENSE file for copyright and license details.
+
+#![deny(non_camel_case_types)]
+#![deny(non_uppercase_statics)]
+#![deny(unnecessary_qualification)]
+#![deny(unnecessary_typecast)]
+
+extern crate native;
+extern crate time;
+extern crate rand;
+extern crate cgmath;
+extern crate getopts;
+extern crate core;
+
+use std::os;
+use std::cmp::max;
+use time::precise_time_ns;
+use rand::{Rng, SeedableRng, XorShiftRng};
+use cgmath::vector::Vec2;
+use getopts::{optopt, optflag, getopts, usage, OptGroup};
+use core::core::Unit;
+use core::types::{MBool, MInt, UnitId, PlayerId, MapPos, Size2};
+use core::game_state::GameState;
+use core::rules::Rules;
+use core::pathfinder::Pathfinder;
+
+struct Options {
+    map_size: Size2<MInt>,
+    queries: MInt,
+    units: MInt,
+    seed: u32,
+}
+
+fn get_opts() -> Vec<OptGroup> {
+    vec!(
+        optopt("", "size", "size of map, default is 256x256", "WxH"),
+        optopt("n", "queries", "paths per benchmark, default is 100", "N"),
+        optopt("u", "units", "enemy units on map, default is 64", "N"),
+        optopt("s", "seed", "seed of random positions, default is 0",
+            "SEED"),
+        optflag("h", "help", "print this help"),
+    )
+}
+
+fn parse_num<T: FromStr>(
+    name: &str,
+    value: Option<~str>,
+    default: T
+) -> Result<T, ~str> {
+    match value {
+        Some(value) => match from_str(value.as_slice()) {
+            Some(n) => Ok(n),
+            None => Err(format!("bad {}: '{}'", name, value)),
+        },
+        None => Ok(default),
+    }
+}
+
+fn parse_size(value: Option<~str>) -> Result<Size2<MInt>, ~str> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(Size2{w: 256, h: 256}),
+    };
+    let mut parts = value.split('x');
+    let w = parts.next().and_then(|s| from_str(s));
+    let h = parts.next().and_then(|s| from_str(s));
+    match (w, h, parts.next()) {
+        (Some(w), Some(h), None) if w > 0 && h > 0 => Ok(Size2{w: w, h: h}),
+        _ => Err(format!("bad map size: '{}'", value)),
+    }
+}
+
+fn parse_options(args: &[~str]) -> Result<Option<Options>, ~str> {
+    let opts = get_opts();
+    let matches = match getopts(args.tail(), opts.as_slice()) {
+        Ok(matches) => matches,
+        Err(f) => return Err(f.to_err_msg()),
+    };
+    if matches.opt_present("h") {
+        let brief = format!("Usage: {} [options]", args[0]);
+        println!("{}", usage(brief.as_slice(), opts.as_slice()));
+        return Ok(None);
+    }
+    Ok(Some(Options {
+        map_size: try!(parse_size(matches.opt_str("size"))),
+        queries: try!(parse_num("queries count", matches.opt_str("n"), 100)),
+        units: try!(parse_num("units count", matches.opt_str("u"), 64)),
+        seed: try!(parse_num("seed", matches.opt_str("s"), 0)),
+    }))
+}
+
+fn random_pos(rng: &mut XorShiftRng, map_size: Size2<MInt>) -> MapPos {
+    Vec2 {
+        x: rng.gen_range(0, map_size.w),
+        y: rng.gen_range(0, map_size.h),
+    }
+}
+
+fn new_rng(options: &Options) -> XorShiftRng {
+    SeedableRng::from_seed(
+        [0x6d2b79f5 ^ options.seed, 0x1b873593, 0xcc9e2d51, 0x85ebca6b])
+}
+
+fn get_queries(options: &Options) -> Vec<(MapPos, MapPos)> {
+    let mut rng = new_rng(options);
+    range(0, options.queries).map(|_| {
+        let from = random_pos(&mut rng, options.map_size);
+        let to = random_pos(&mut rng, options.map_size);
+        (from, to)
+    }).collect()
+}
+
+// Enemies of units that paths are searched for
+fn get_state(options: &Options) -> GameState {
+    let mut rng = new_rng(options);
+    let mut state = GameState::new(Rules::new());
+    for i in range(0, options.units) {
+        let id = UnitId(i + 1);
+        state.units.insert(id, Unit {
+            id: id,
+            pos: random_pos(&mut rng, options.map_size),
+            player_id: PlayerId(1),
+        });
+    }
+    state
+}
+
+fn new_unit(pos: MapPos) -> Unit {
+    Unit {
+        id: UnitId(0),
+        pos: pos,
+        player_id: PlayerId(0),
+    }
+}
+
+// Returns total cost of all found paths so that results
+// of different algorithms can be compared
+fn bench_fill_map(
+    map_size: Size2<MInt>,
+    state: &GameState,
+    queries: &[(MapPos, MapPos)]
+) -> MInt {
+    let mut pathfinder = Pathfinder::new(map_size);
+    let mut total_cost = 0;
+    for &(from, to) in queries.iter() {
+        pathfinder.fill_map(state, &new_unit(from));
+        if pathfinder.can_stop_at(to) {
+            let _ = pathfinder.get_path(to);
+            total_cost += pathfinder.get_cost(to).unwrap();
+        }
+    }
+    total_cost
+}
+
+fn bench_find_path(
+    map_size: Size2<MInt>,
+    state: &GameState,
+    queries: &[(MapPos, MapPos)]
+) -> MInt {
+    let mut pathfinder = Pathfinder::new(map_size);
+    let mut total_cost = 0;
+    for &(from, to) in queries.iter() {
+        let unit = new_unit(from);
+        match pathfinder.find_path(state, &unit, from, to) {
+            Some(path) => total_cost += path.cost,
+            None => {},
+        }
+    }
+    total_cost
+}
+
+// Same unit asks for paths to different tiles, like
+// an AI that looks for the best target: map is filled once
+fn bench_cached_fill_map(
+    map_size: Size2<MInt>,
+    state: &GameState,
+    queries: &[(MapPos, MapPos)]
+) -> MInt {
+    let mut pathfinder = Pathfinder::new(map_size);
+    let (from, _) = queries[0];
+    let unit = new_unit(from);
+    let mut total_cost = 0;
+    for &(_, to) in queries.iter() {
+        pathfinder.fill_map(state, &unit);
+        if pathfinder.can_stop_at(to) {
+            let _ = pathfinder.get_path(to);
+            total_cost += pathfinder.get_cost(to).unwrap();
+        }
+    }
+    total_cost
+}
+
+// Returns total number of tiles reachable in one turn
+fn bench_reachable(
+    map_size: Size2<MInt>,
+    state: &GameState,
+    queries: &[(MapPos, MapPos)],
+    is_limited: MBool
+) -> MInt {
+    let mut pathfinder = Pathfinder::new(map_size);
+    let mut tiles_count = 0;
+    let move_distance = state.rules.move_distance;
+    for &(from, _) in queries.iter() {
+        let unit = new_unit(from);
+        if is_limited {
+            pathfinder.fill_map_within(state, &unit, move_distance);
+        } else {
+            pathfinder.fill_map(state, &unit);
+        }
+        tiles_count += pathfinder.get_reachable(move_distance).len() as MInt;
+    }
+    tiles_count
+}
+
+// Positions of all enemies, sorted by id
+fn get_targets(state: &GameState) -> Vec<MapPos> {
+    let mut ids: Vec<UnitId> = state.units.keys().map(|id| *id).collect();
+    ids.sort();
+    ids.iter().map(|id| state.units.get(id).pos).collect()
+}
+
+// Every unit looks for the closest enemy. Returns total cost of paths.
+fn bench_closest_target(
+    map_size: Size2<MInt>,
+    state: &GameState,
+    queries: &[(MapPos, MapPos)]
+) -> MInt {
+    let targets = get_targets(state);
+    let mut pathfinder = Pathfinder::new(map_size);
+    let mut total_cost = 0;
+    for &(from, _) in queries.iter() {
+        pathfinder.fill_map(state, &new_unit(from));
+        let mut best_cost = None;
+        for pos in targets.iter() {
+            match (pathfinder.get_cost(*pos), best_cost) {
+                (Some(cost), Some(best)) if cost >= best => {},
+                (Some(cost), _) => best_cost = Some(cost),
+                (None, _) => {},
+            }
+        }
+        total_cost += best_cost.unwrap_or(0);
+    }
+    total_cost
+}
+
+fn bench_flow_field(
+    map_size: Size2<MInt>,
+    state: &GameState,
+    queries: &[(MapPos, MapPos)]
+) -> MInt {
+    let targets = get_targets(state);
+    let mut pathfinder = Pathfinder::new(map_size);
+    let mut total_cost = 0;
+    for &(from, _) in queries.iter() {
+        let unit = new_unit(from);
+        pathfinder.fill_flow_field(state, &unit, targets.as_slice());
+        let _ = pathfinder.get_flow_path(from);
+        total_cost += pathfinder.get_cost(from).unwrap_or(0);
+    }
+    total_cost
+}
+
+fn print_speedup(name: &str, slow_time: u64, fast_time: u64) {
+    let speedup = slow_time as f64 / max(fast_time, 1) as f64;
+    println!("{} speedup: {:.1f}x", name, speedup);
+}
+
+// Runs benchmark and prints time per query, returns its result
+fn measure(
+    name: &str,
+    queries_count: MInt,
+    bench: || -> MInt
+) -> (MInt, u64) {
+    let start = precise_time_ns();
+    let result = bench();
+    let time = precise_time_ns() - start;
+    let per_query = time / queries_count as u64;
+    println!("{}: {} us per query", name, per_query / 1000);
+    (result, time)
+}
+
+fn run(options: &Options) -> Result<(), ~str> {
+    let map_size = options.map_size;
+    let state = get_state(options);
+    let queries = get_queries(options);
+    let queries = queries.as_slice();
+    let n = options.queries;
+    if n <= 0 {
+        return Err(~"at least one query is required");
+    }
+    println!("map: {}x{}, queries: {}, units: {}",
+        map_size.w, map_size.h, n, options.units);
+    let (fill_cost, fill_time) = measure("fill_map + get_path", n,
+        || bench_fill_map(map_size, &state, queries));
+    let (astar_cost, astar_time) = measure("find_path (A*)", n,
+        || bench_find_path(map_size, &state, queries));
+    if fill_cost != astar_cost {
+        return Err(format!("A* found worse paths: total cost {} \
+            instead of {}", astar_cost, fill_cost));
+    }
+    print_speedup("A*", fill_time, astar_time);
+    let (_, cached_time) = measure("fill_map of one unit + get_path", n,
+        || bench_cached_fill_map(map_size, &state, queries));
+    print_speedup("Cached fill", fill_time, cached_time);
+    let (full_count, full_time) = measure("fill_map + get_reachable", n,
+        || bench_reachable(map_size, &state, queries, false));
+    let (limited_count, limited_time) = measure(
+        "fill_map_within + get_reachable", n,
+        || bench_reachable(map_size, &state, queries, true));
+    if full_count != limited_count {
+        return Err(format!("limited fill found {} reachable tiles \
+            instead of {}", limited_count, full_count));
+    }
+    print_speedup("Limited fill", full_time, limited_time);
+    let (closest_cost, closest_time) = measure(
+        "fill_map + closest enemy", n,
+        || bench_closest_target(map_size, &state, queries));
+    let (flow_cost, flow_time) = measure("fill_flow_field", n,
+        || bench_flow_field(map_size, &state, queries));
+    if closest_cost != flow_cost {
+        return Err(format!("flow field found worse paths: total cost {} \
+            instead of {}", flow_cost, closest_cost));
+    }
+    print_speedup("Flow field", closest_time, flow_time);
+    Ok(())
+}
+
+fn main() {
+    let args = os::args();
+    let result = match parse_options(args.as_slice()) {
+        Ok(Some(options)) => run(&options),
+        Ok(None) => Ok(()),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => {},
+        Err(e) => {
+            println!("Error: {}", e);
+            os::set_exit_status(1);
+        },
+    }
+}
+
+#[start]
+fn start(argc: int, argv: **u8) -> int {
+    native::start(argc, argv, main)
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -0,0 +1,90 @@
+// See LICENSE file for copyright and license details.
+
+use collections::hashmap::HashMap;
+use types::{MInt, UnitId, PlayerId, Size2};
+use core::{
+    CoreInterface,
+    Command,
+    CommandMove,
+    CommandAttackUnit,
+    Event,
+    EventMove,
+    EventAttackUnit,
+    PlayerAi,
+    PlayerHuman,
+};
+use game_state::GameState;
+use greedy_ai::GreedyAi;
+use minimax_ai::MinimaxAi;
+
+pub static AI_NAMES: [&'static str, ..2] = ["greedy", "minimax"];
+
+pub trait Ai {
+    // Returns all commands of one turn, the last one is CommandEndTurn.
+    // `events` holds everything player received since previous call.
+    fn take_turn(
+        &mut self,
+        state: &GameState,
+        events: &[Event]
+    ) -> Vec<Command>;
+}
+
+pub fn new_ai(
+    name: &str,
+    player_id: PlayerId,
+    map_size: Size2<MInt>
+) -> Result<~Ai, ~str> {
+    match name {
+        "greedy" => Ok(~GreedyAi::new(player_id, map_size) as ~Ai),
+        "minimax" => Ok(~MinimaxAi::new(player_id, map_size) as ~Ai),
+        _ => Err(format!("unknown AI: '{}', expected one of: {}",
+            name, AI_NAMES.connect(", "))),
+    }
+}
+
+// Names are checked when scenario is loaded (Scenario::check_players),
+// so unknown AI here is a bug in code that made the scenario
+pub fn get_ais(core: &CoreInterface) -> HashMap<PlayerId, ~Ai> {
+    let mut m = HashMap::new();
+    for i in range(0, core.players_count()) {
+        let player_id = PlayerId(i);
+        match core.player_kind(player_id) {
+            PlayerAi(name) => {
+                let map_size = core.map_size();
+                match new_ai(name.as_slice(), player_id, map_size) {
+                    Ok(ai) => m.insert(player_id, ai),
+                    Err(e) => fail!("Scenario is not checked: {}", e),
+                };
+            },
+            PlayerHuman => {},
+        }
+    }
+    m
+}
+
+// Sorted by id so AI`s decisions do not depend on HashMap`s order
+pub fn player_units(state: &GameState, player_id: PlayerId) -> Vec<UnitId> {
+    let mut ids = Vec::new();
+    for (id, unit) in state.units.iter() {
+        if unit.player_id == player_id {
+            ids.push(*id);
+        }
+    }
+    ids.sort();
+    ids
+}
+
+// Updates AI`s copy of state as if Core has executed the command
+pub fn simulate_command(state: &mut GameState, command: &Command) {
+    match *command {
+        CommandMove(unit_id, ref path) => {
+            state.apply_event(&EventMove(unit_id, path.clone()));
+        },
+        CommandAttackUnit(attacker_id, defender_id) => {
+            state.apply_event(&EventAttackUnit(attacker_id, defender_id));
+        },
+        _ => {},
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -0,0 +1,167 @@
+// See LICENSE file for copyright and license details.
+
+use collections::hashmap::HashMap;
+use types::{MBool, MInt, UnitId, PlayerId, MapPos};
+use core::{CoreState, Unit, Event};
+use game_state::GameState;
+use rules::Rules;
+use encoding::event_to_json;
+
+// Player`s game state does not match what Core thinks player knows
+pub struct Divergence {
+    pub player_id: PlayerId,
+    pub event: Event, // first event after which states differ
+    pub message: ~str,
+}
+
+impl Divergence {
+    pub fn to_str(&self) -> ~str {
+        let PlayerId(id) = self.player_id;
+        format!("player {}: {} after event {}",
+            id, self.message, event_to_json(&self.event).to_str())
+    }
+}
+
+fn pos_to_str(unit: &Unit) -> ~str {
+    format!("{},{}", unit.pos.x, unit.pos.y)
+}
+
+fn path_to_str(path: &[MapPos]) -> ~str {
+    let positions: Vec<~str> = path.iter()
+        .map(|pos| format!("{},{}", pos.x, pos.y))
+        .collect();
+    positions.connect(" ")
+}
+
+// Returns description of the first found difference
+fn compare_units(
+    expected: &HashMap<UnitId, Unit>,
+    state: &GameState
+) -> Option<~str> {
+    let mut ids: Vec<UnitId> = expected.keys().map(|id| *id).collect();
+    ids.extend(state.units.keys().map(|id| *id));
+    ids.sort();
+    ids.dedup();
+    for id in ids.iter() {
+        let UnitId(n) = *id;
+        match (expected.find(id), state.units.find(id)) {
+            (Some(_), None) => {
+                return Some(format!("unit {} is missing", n));
+            },
+            (None, Some(_)) => {
+                return Some(format!("unit {} does not exist", n));
+            },
+            (Some(a), Some(b)) => {
+                if a.pos != b.pos {
+                    return Some(format!("unit {} is at {} instead of {}",
+                        n, pos_to_str(b), pos_to_str(a)));
+                }
+                if a.player_id != b.player_id {
+                    let PlayerId(a_id) = a.player_id;
+                    let PlayerId(b_id) = b.player_id;
+                    return Some(format!("unit {} belongs to player {} \
+                        instead of {}", n, b_id, a_id));
+                }
+            },
+            (None, None) => {},
+        }
+    }
+    None
+}
+
+// Both sides keep only the rest of the order`s path
+fn compare_move_orders(
+    expected: &CoreState,
+    state: &GameState
+) -> Option<~str> {
+    let mut ids: Vec<UnitId> = expected.units().keys()
+        .map(|id| *id).collect();
+    ids.extend(state.move_orders.keys().map(|id| *id));
+    ids.sort();
+    ids.dedup();
+    for id in ids.iter() {
+        let UnitId(n) = *id;
+        match (expected.move_order_path(*id), state.move_orders.find(id)) {
+            (Some(_), None) => {
+                return Some(format!("move order of unit {} is missing", n));
+            },
+            (None, Some(_)) => {
+                return Some(format!("unit {} has no move order", n));
+            },
+            (Some(a), Some(b)) => {
+                if a != b.as_slice() {
+                    return Some(format!("move order of unit {} is {} \
+                        instead of {}", n, path_to_str(b.as_slice()),
+                        path_to_str(a)));
+                }
+            },
+            (None, None) => {},
+        }
+    }
+    None
+}
+
+fn compare_states(expected: &CoreState, state: &GameState) -> Option<~str> {
+    match compare_units(expected.units(), state) {
+        Some(message) => Some(message),
+        None => compare_move_orders(expected, state),
+    }
+}
+
+// Keeps shadow copy of every player`s GameState, updated only with
+// GameState::apply_event, and compares it with Core`s units and
+// move orders after every event. Player`s state diverges once and
+// stays broken, so only the first divergence of every player
+// is recorded.
+pub struct ConsistencyChecker {
+    states: HashMap<PlayerId, GameState>,
+    divergences: Vec<Divergence>,
+}
+
+impl ConsistencyChecker {
+    pub fn new(players_count: MInt, rules: Rules) -> ConsistencyChecker {
+        let mut states = HashMap::new();
+        for i in range(0, players_count) {
+            states.insert(PlayerId(i), GameState::new(rules.clone()));
+        }
+        ConsistencyChecker {
+            states: states,
+            divergences: Vec::new(),
+        }
+    }
+
+    pub fn divergences<'a>(&'a self) -> &'a [Divergence] {
+        self.divergences.as_slice()
+    }
+
+    fn is_diverged(&self, player_id: PlayerId) -> MBool {
+        self.divergences.iter().any(|d| d.player_id == player_id)
+    }
+
+    // There is no fog of war yet: every player knows about every unit
+    pub fn check(&mut self, expected: &CoreState, event: &Event) {
+        let mut ids: Vec<PlayerId> = self.states.keys()
+            .map(|id| *id).collect();
+        ids.sort();
+        for player_id in ids.iter() {
+            if self.is_diverged(*player_id) {
+                continue;
+            }
+            let message = {
+                let state = self.states.get_mut(player_id);
+                state.apply_event(event);
+                compare_states(expected, state)
+            };
+            match message {
+                Some(message) => self.divergences.push(Divergence {
+                    player_id: *player_id,
+                    event: event.clone(),
+                    message: message,
+                }),
+                None => {},
+            }
+        }
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
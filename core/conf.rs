@@ -1,36 +1,220 @@
 // See LICENSE file for copyright and license details.
 
+use std::os;
+use collections::treemap::TreeMap;
 use serialize::{Decodable, json};
-use core::misc::read_file;
+use misc::try_read_file;
 
-pub struct Config {
+pub enum ConfigError {
+    ConfigErrorRead(~str, ~str), // path, reason
+    ConfigErrorParse(~str), // source that is not a JSON object
+    ConfigErrorNoField(~str, ~str), // all sources, field
+    ConfigErrorBadField(~str, ~str, ~str), // source, field, reason
+    ConfigErrorBadOverride(~str), // not a `name=value` pair
+}
+
+impl ConfigError {
+    pub fn to_str(&self) -> ~str {
+        match *self {
+            ConfigErrorRead(ref path, ref reason) => {
+                format!("{}: {}", path, reason)
+            },
+            ConfigErrorParse(ref source) => {
+                format!("{}: config is not a valid JSON object", source)
+            },
+            ConfigErrorNoField(ref sources, ref field) => {
+                format!("no field '{}' in {}", field, sources)
+            },
+            ConfigErrorBadField(ref source, ref field, ref reason) => {
+                format!("{}: field '{}': {}", source, field, reason)
+            },
+            ConfigErrorBadOverride(ref text) => {
+                format!("bad override '{}', expected name=value", text)
+            },
+        }
+    }
+}
+
+fn decoder_error_to_str(error: json::Error) -> ~str {
+    match error {
+        json::ExpectedError(expected, found) => {
+            format!("expected {}, found {}", expected, found)
+        },
+        json::MissingFieldError(field) => {
+            format!("missing field '{}'", field)
+        },
+        _ => ~"bad value",
+    }
+}
+
+fn parse_object(
+    source: &str,
+    text: &str
+) -> Result<~json::Object, ConfigError> {
+    match json::from_str(text) {
+        Ok(json::Object(obj)) => Ok(obj),
+        _ => Err(ConfigErrorParse(source.to_owned())),
+    }
+}
+
+// Where user`s copy of config file is looked for
+fn user_config_path(path: &Path) -> Option<Path> {
+    match (os::homedir(), path.filename()) {
+        (Some(dir), Some(name)) => Some(dir.join(".marauder").join(name)),
+        _ => None,
+    }
+}
+
+struct Layer {
+    source: ~str, // file path, "defaults" or "command line"
     json: ~json::Object,
 }
 
-fn decode<A: Decodable<json::Decoder, json::Error>>(json_obj: json::Json) -> A {
-    let mut decoder = json::Decoder::new(json_obj);
-    let decoded: A = Decodable::decode(&mut decoder).unwrap();
-    decoded
+// Layers are added from the least to the most important one:
+// defaults, bundled file, user`s file and command line.
+// Field is taken from the last layer that has it.
+pub struct Config {
+    layers: Vec<Layer>,
 }
 
 impl Config {
-    pub fn new(path: &str) -> Config {
-        let path = Path::new(path);
-        let json = match json::from_str(read_file(&path)) {
-            Ok(json::Object(obj)) => obj,
-            _ => fail!("Config error"),
+    pub fn new() -> Config {
+        let mut config = Config {
+            layers: Vec::new(),
         };
-        Config {
+        config.add_layer("defaults", ~TreeMap::new());
+        config
+    }
+
+    fn add_layer(&mut self, source: &str, json: ~json::Object) {
+        self.layers.push(Layer {
+            source: source.to_owned(),
             json: json,
+        });
+    }
+
+    fn find_layer<'a>(&'a mut self, source: &str) -> Option<&'a mut Layer> {
+        self.layers.mut_iter().find(|layer| {
+            layer.source.as_slice() == source
+        })
+    }
+
+    fn set_in_layer(&mut self, source: &str, name: &str, value: json::Json) {
+        if self.find_layer(source).is_none() {
+            self.add_layer(source, ~TreeMap::new());
         }
+        let layer = self.find_layer(source).unwrap();
+        layer.json.insert(name.to_owned(), value);
     }
 
-    pub fn get<A: Decodable<json::Decoder, json::Error>>(&self, name: &str) -> A {
+    // Defaults are written by programmers, so bad JSON is a bug
+    pub fn set_default(&mut self, name: &str, value: &str) {
+        let value = match json::from_str(value) {
+            Ok(value) => value,
+            Err(_) => fail!("Bad default value of '{}': {}", name, value),
+        };
+        self.set_in_layer("defaults", name, value);
+    }
+
+    pub fn add_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let source = format!("{}", path.display());
+        let text = match try_read_file(path) {
+            Ok(text) => text,
+            Err(e) => return Err(ConfigErrorRead(source, e)),
+        };
+        let json = try!(parse_object(source.as_slice(), text.as_slice()));
+        self.add_layer(source.as_slice(), json);
+        Ok(())
+    }
+
+    // User`s file is not required to exist
+    pub fn add_optional_file(
+        &mut self,
+        path: &Path
+    ) -> Result<(), ConfigError> {
+        if path.exists() {
+            self.add_file(path)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Value is JSON or, if it is not, a plain string:
+    // `screen_size={"w":800,"h":600}` or `players=["human","greedy"]`
+    pub fn add_override(&mut self, text: &str) -> Result<(), ConfigError> {
+        let (name, value) = match text.find('=') {
+            Some(i) if i > 0 => (text.slice_to(i), text.slice_from(i + 1)),
+            _ => return Err(ConfigErrorBadOverride(text.to_owned())),
+        };
+        let value = match json::from_str(value) {
+            Ok(value) => value,
+            Err(_) => json::String(value.to_owned()),
+        };
+        self.set_in_layer("command line", name, value);
+        Ok(())
+    }
+
+    pub fn add_overrides(
+        &mut self,
+        overrides: &[~str]
+    ) -> Result<(), ConfigError> {
+        for text in overrides.iter() {
+            try!(self.add_override(text.as_slice()));
+        }
+        Ok(())
+    }
+
+    // Bundled file, user`s copy of it and command line
+    pub fn add_layers(
+        &mut self,
+        path: &str,
+        overrides: &[~str]
+    ) -> Result<(), ConfigError> {
+        let path = Path::new(path);
+        try!(self.add_file(&path));
+        match user_config_path(&path) {
+            Some(path) => try!(self.add_optional_file(&path)),
+            None => {},
+        }
+        self.add_overrides(overrides)
+    }
+
+    fn sources(&self) -> ~str {
+        let sources: Vec<~str> = self.layers.iter()
+            .map(|layer| layer.source.clone())
+            .collect();
+        sources.connect(", ")
+    }
+
+    // Layer that field is taken from, for errors about its value
+    pub fn source_of(&self, name: &str) -> ~str {
         let owned_name_str = name.into_owned();
-        decode(match self.json.find(&owned_name_str) {
-            Some(val) => val.clone(),
-            None => fail!("No field '{}", name),
-        })
+        for layer in self.layers.iter().rev() {
+            if layer.json.contains_key(&owned_name_str) {
+                return layer.source.clone();
+            }
+        }
+        self.sources()
+    }
+
+    pub fn get<A: Decodable<json::Decoder, json::Error>>(
+        &self,
+        name: &str
+    ) -> Result<A, ConfigError> {
+        let owned_name_str = name.into_owned();
+        for layer in self.layers.iter().rev() {
+            let value = match layer.json.find(&owned_name_str) {
+                Some(value) => value.clone(),
+                None => continue,
+            };
+            let mut decoder = json::Decoder::new(value);
+            return match Decodable::decode(&mut decoder) {
+                Ok(decoded) => Ok(decoded),
+                Err(e) => Err(ConfigErrorBadField(layer.source.clone(),
+                    owned_name_str, decoder_error_to_str(e))),
+            };
+        }
+        Err(ConfigErrorNoField(self.sources(), owned_name_str))
     }
 }
 
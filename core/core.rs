@@ -2,85 +2,342 @@
 
 use collections::hashmap::HashMap;
 use cgmath::vector::Vec2;
-use core::types::{Size2, MInt, UnitId, PlayerId, MapPos};
-use core::conf::Config;
-
+use types::{Size2, MBool, MInt, UnitId, PlayerId, SubscriberId, MapPos};
+use scenario::Scenario;
+use conf::ConfigError;
+use checker::{ConsistencyChecker, Divergence};
+use observer::{EventLog, Delivery, DeliveryPoll};
+use game_state::check_command;
+use map::distance;
+use rules::Rules;
+
+#[deriving(Clone)]
 pub enum Command {
     CommandMove(UnitId, Vec<MapPos>),
     CommandEndTurn,
     CommandCreateUnit(MapPos),
     CommandAttackUnit(UnitId, UnitId),
+    CommandSetMoveOrder(UnitId, Vec<MapPos>), // whole path to destination
 }
 
+#[deriving(Clone)]
 pub enum Event {
     EventMove(UnitId, Vec<MapPos>),
     EventEndTurn(PlayerId, PlayerId), // old_id, new_id
     EventCreateUnit(UnitId, MapPos, PlayerId),
     EventAttackUnit(UnitId, UnitId),
+    EventMoveOrder(UnitId, Vec<MapPos>), // rest of path, empty if done
+}
+
+#[deriving(Clone)]
+pub enum PlayerKind {
+    PlayerHuman,
+    PlayerAi(~str), // name of AI strategy
 }
 
+impl PlayerKind {
+    pub fn from_str(name: &str) -> PlayerKind {
+        match name {
+            "human" => PlayerHuman,
+            ai_name => PlayerAi(ai_name.to_owned()),
+        }
+    }
+
+    pub fn name(&self) -> ~str {
+        match *self {
+            PlayerHuman => ~"human",
+            PlayerAi(ref ai_name) => ai_name.clone(),
+        }
+    }
+}
+
+#[deriving(Clone)]
 pub struct Player {
     pub id: PlayerId,
+    pub kind: PlayerKind,
 }
 
+#[deriving(Clone)]
 pub struct Unit {
     pub id: UnitId,
     pub pos: MapPos,
     pub player_id: PlayerId,
 }
 
-pub struct Core {
+// Standing order to move along the path for several turns
+#[deriving(Clone)]
+struct MoveOrder {
+    path: Vec<MapPos>, // starts at unit`s current position
+    known_enemies: Vec<UnitId>, // seen when order was given or updated
+}
+
+fn get_players(kinds: &Vec<PlayerKind>) -> Vec<Player> {
+    let mut players = Vec::new();
+    for (i, kind) in kinds.iter().enumerate() {
+        players.push(Player {
+            id: PlayerId(i as MInt),
+            kind: kind.clone(),
+        });
+    }
+    players
+}
+
+// Events made by one command. Checker, if there is one, compares
+// players` states with CoreState right after every event.
+struct EventOutput<'a> {
+    events: Vec<Event>,
+    checker: Option<&'a mut ConsistencyChecker>,
+}
+
+impl<'a> EventOutput<'a> {
+    fn new(checker: Option<&'a mut ConsistencyChecker>) -> EventOutput<'a> {
+        EventOutput {
+            events: Vec::new(),
+            checker: checker,
+        }
+    }
+
+    fn push(&mut self, state: &CoreState, event: Event) {
+        match self.checker {
+            Some(ref mut checker) => checker.check(state, &event),
+            None => {},
+        }
+        self.events.push(event);
+    }
+}
+
+// Whole state of the game without event queues, subscribers or
+// config. Cloning it is a cheap fork, so AIs and tools can try
+// commands on a copy without touching the real game.
+#[deriving(Clone)]
+pub struct CoreState {
     units: HashMap<UnitId, Unit>,
     players: Vec<Player>,
     current_player_id: PlayerId,
-    core_event_list: Vec<~CoreEvent>,
-    event_lists: HashMap<PlayerId, Vec<Event>>,
+    next_unit_id: MInt, // ids of dead units are never reused
+    move_orders: HashMap<UnitId, MoveOrder>,
     map_size: Size2<MInt>,
+    rules: Rules,
 }
 
-fn get_event_lists() -> HashMap<PlayerId, Vec<Event>> {
-    let mut map = HashMap::new();
-    map.insert(PlayerId(0), Vec::new());
-    map.insert(PlayerId(1), Vec::new());
-    map
+// Pure step: returns new state and events of the command,
+// `state` is not changed
+pub fn apply(
+    state: &CoreState,
+    command: Command
+) -> Result<(CoreState, Vec<Event>), ~str> {
+    let mut new_state = state.clone();
+    let events = try!(new_state.execute(command));
+    Ok((new_state, events))
 }
 
-impl Core {
-    pub fn new() -> ~Core {
-        let config = Config::new("conf_core.json");
-        let map_size = config.get("map_size");
-        let mut core = ~Core {
+impl CoreState {
+    // Scenario`s units are created by events, they are dropped here
+    pub fn from_scenario(scenario: &Scenario, rules: Rules) -> CoreState {
+        let mut state = CoreState::without_units(scenario, rules);
+        state.add_units(scenario, &mut EventOutput::new(None));
+        state
+    }
+
+    fn without_units(scenario: &Scenario, rules: Rules) -> CoreState {
+        CoreState {
             units: HashMap::new(),
-            players: vec!(Player{id: PlayerId(0)}, Player{id: PlayerId(1)}),
+            players: get_players(&scenario.players),
             current_player_id: PlayerId(0),
-            core_event_list: Vec::new(),
-            event_lists: get_event_lists(),
-            map_size: map_size,
-        };
-        core.add_unit(Vec2{x: 0, y: 0}, PlayerId(0));
-        core.add_unit(Vec2{x: 0, y: 1}, PlayerId(0));
-        core.add_unit(Vec2{x: 2, y: 0}, PlayerId(1));
-        core.add_unit(Vec2{x: 2, y: 2}, PlayerId(1));
-        core
+            next_unit_id: 0,
+            move_orders: HashMap::new(),
+            map_size: scenario.map_size,
+            rules: rules,
+        }
     }
 
-    fn add_unit(&mut self, pos: MapPos, player_id: PlayerId) {
-        let core_event = CoreEventCreateUnit::new(
-            self, pos, player_id);
-        self.do_core_event(core_event);
+    fn add_units(&mut self, scenario: &Scenario, out: &mut EventOutput) {
+        for unit in scenario.units.iter() {
+            let pos = Vec2{x: unit.x, y: unit.y};
+            let core_event = CoreEventCreateUnit::new(
+                self, pos, PlayerId(unit.player_id));
+            self.do_core_event(core_event, out);
+        }
+    }
+
+    pub fn units<'a>(&'a self) -> &'a HashMap<UnitId, Unit> {
+        &self.units
     }
 
     pub fn map_size(&self) -> Size2<MInt> {
         self.map_size
     }
 
+    pub fn rules<'a>(&'a self) -> &'a Rules {
+        &self.rules
+    }
+
+    pub fn move_order_path<'a>(
+        &'a self,
+        unit_id: UnitId
+    ) -> Option<&'a [MapPos]> {
+        self.move_orders.find(&unit_id).map(|order| order.path.as_slice())
+    }
+
+    pub fn players_count(&self) -> MInt {
+        self.players.len() as MInt
+    }
+
+    pub fn player_kind(&self, player_id: PlayerId) -> PlayerKind {
+        let PlayerId(id) = player_id;
+        self.players.get(id as uint).kind.clone()
+    }
+
     pub fn player_id(&self) -> PlayerId {
         self.current_player_id
     }
 
-    pub fn get_event(&mut self) -> Option<Event> {
-        let list = self.event_lists.get_mut(&self.current_player_id);
-        list.shift()
+    // Returns Some(player_id) when only one player still has units
+    pub fn winner(&self) -> Option<PlayerId> {
+        let mut winner = None;
+        for (_, unit) in self.units.iter() {
+            match winner {
+                Some(player_id) if player_id != unit.player_id => {
+                    return None;
+                },
+                _ => winner = Some(unit.player_id),
+            }
+        }
+        winner
+    }
+
+    // Applies command of current player and returns its events.
+    // Invalid command is rejected before anything is changed.
+    pub fn execute(&mut self, command: Command) -> Result<Vec<Event>, ~str> {
+        let mut out = EventOutput::new(None);
+        try!(self.do_command(command, &mut out));
+        Ok(out.events)
+    }
+
+    // Events that create all existing units and their move orders
+    fn units_snapshot(&self) -> Vec<Event> {
+        let mut unit_ids: Vec<UnitId> = self.units.keys()
+            .map(|id| *id).collect();
+        unit_ids.sort();
+        let mut events: Vec<Event> = unit_ids.iter().map(|id| {
+            let unit = self.units.get(id);
+            EventCreateUnit(unit.id, unit.pos, unit.player_id)
+        }).collect();
+        for id in unit_ids.iter() {
+            match self.move_orders.find(id) {
+                Some(order) => {
+                    events.push(EventMoveOrder(*id, order.path.clone()));
+                },
+                None => {},
+            }
+        }
+        events
+    }
+
+    // Enemies that unit can see
+    fn enemies_in_sight(&self, unit_id: UnitId) -> Vec<UnitId> {
+        let unit = self.units.get(&unit_id);
+        let sight = self.rules.sight();
+        let mut ids: Vec<UnitId> = self.units.iter()
+            .filter(|&(_, u)| u.player_id != unit.player_id
+                && distance(u.pos, unit.pos) <= sight)
+            .map(|(id, _)| *id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn units_at_count(&self, pos: MapPos) -> uint {
+        self.units.iter().filter(|&(_, u)| u.pos == pos).count()
+    }
+
+    fn is_enemy_at(&self, player_id: PlayerId, pos: MapPos) -> MBool {
+        self.units.iter()
+            .any(|(_, u)| u.pos == pos && u.player_id != player_id)
+    }
+
+    // Returns number of steps unit moves this turn or None if
+    // order must be cancelled. Core has no terrain, so every step
+    // costs the same and unit makes move_distance steps per turn.
+    fn get_order_steps(&self, unit_id: UnitId) -> Option<uint> {
+        let order = self.move_orders.get(&unit_id);
+        let unit = self.units.get(&unit_id);
+        let is_new_enemy_seen = self.enemies_in_sight(unit_id).iter()
+            .any(|id| !order.known_enemies.contains(id));
+        if is_new_enemy_seen || *order.path.get(0) != unit.pos {
+            return None;
+        }
+        let max_steps = self.rules.move_distance as uint;
+        for (i, pos) in order.path.iter().enumerate().skip(1) {
+            if i > max_steps {
+                break;
+            }
+            if self.is_enemy_at(unit.player_id, *pos) {
+                return None;
+            }
+        }
+        let mut steps = order.path.len() - 1;
+        if steps > max_steps {
+            steps = max_steps;
+        }
+        while steps > 0 && self.units_at_count(*order.path.get(steps))
+            >= self.rules.max_units_per_tile
+        {
+            steps -= 1;
+        }
+        if steps == 0 {
+            None
+        } else {
+            Some(steps)
+        }
+    }
+
+    fn cancel_move_order(&mut self, unit_id: UnitId, out: &mut EventOutput) {
+        if self.move_orders.contains_key(&unit_id) {
+            let core_event = CoreEventMoveOrder::new(
+                self, unit_id, Vec::new());
+            self.do_core_event(core_event, out);
+        }
+    }
+
+    // Moves unit along its order`s path for one turn
+    fn execute_move_order(&mut self, unit_id: UnitId, out: &mut EventOutput) {
+        if self.move_orders.find(&unit_id).is_none() {
+            return;
+        }
+        let steps = match self.get_order_steps(unit_id) {
+            Some(steps) => steps,
+            None => {
+                self.cancel_move_order(unit_id, out);
+                return;
+            },
+        };
+        let (segment, rest) = {
+            let path = self.move_orders.get(&unit_id).path.as_slice();
+            let rest = if steps + 1 == path.len() {
+                Vec::new()
+            } else {
+                Vec::from_slice(path.slice_from(steps))
+            };
+            (Vec::from_slice(path.slice_to(steps + 1)), rest)
+        };
+        let core_event = CoreEventMove::new(self, unit_id, segment);
+        self.do_core_event(core_event, out);
+        let core_event = CoreEventMoveOrder::new(self, unit_id, rest);
+        self.do_core_event(core_event, out);
+    }
+
+    // Orders are carried out at the start of owner`s turn
+    fn execute_move_orders(&mut self, out: &mut EventOutput) {
+        let player_id = self.current_player_id;
+        let mut unit_ids: Vec<UnitId> = self.move_orders.keys()
+            .filter(|id| self.units.get(*id).player_id == player_id)
+            .map(|id| *id)
+            .collect();
+        unit_ids.sort();
+        for unit_id in unit_ids.iter() {
+            self.execute_move_order(*unit_id, out);
+        }
     }
 
     fn command_to_core_event(&self, command: Command) -> ~CoreEvent {
@@ -105,33 +362,239 @@ impl Core {
                     defender_id,
                 ) as ~CoreEvent
             },
+            CommandSetMoveOrder(unit_id, path) => {
+                CoreEventMoveOrder::new(self, unit_id, path) as ~CoreEvent
+            },
         }
     }
 
-    pub fn do_command(&mut self, command: Command) {
+    fn do_core_event(
+        &mut self,
+        core_event: ~CoreEvent,
+        out: &mut EventOutput
+    ) {
+        core_event.apply(self);
+        out.push(self, core_event.to_event());
+    }
+
+    fn do_command(
+        &mut self,
+        command: Command,
+        out: &mut EventOutput
+    ) -> Result<(), ~str> {
+        try!(check_command(&self.units, self.map_size, &self.rules,
+            self.current_player_id, &command));
+        let (order_unit_id, is_end_turn) = match command {
+            CommandSetMoveOrder(unit_id, _) => (Some(unit_id), false),
+            CommandEndTurn => (None, true),
+            _ => (None, false),
+        };
+        // Direct move replaces unit`s standing order
+        match command {
+            CommandMove(unit_id, _) => self.cancel_move_order(unit_id, out),
+            _ => {},
+        }
         let core_event = self.command_to_core_event(command);
-        self.do_core_event(core_event);
+        self.do_core_event(core_event, out);
+        match order_unit_id {
+            Some(unit_id) => self.execute_move_order(unit_id, out),
+            None => {},
+        }
+        if is_end_turn {
+            self.execute_move_orders(out);
+        }
+        Ok(())
     }
+}
+
+// Runs the game for players and frontends: CoreState plus
+// queues of events for every player and other subscribers
+pub struct Core {
+    state: CoreState,
+    event_log: EventLog,
+    player_subscribers: HashMap<PlayerId, SubscriberId>,
+    checker: Option<ConsistencyChecker>,
+}
 
-    fn do_core_event(&mut self, core_event: ~CoreEvent) {
-        self.core_event_list.push(core_event);
-        self.make_events();
+fn get_player_subscribers(
+    event_log: &mut EventLog,
+    players: &Vec<Player>
+) -> HashMap<PlayerId, SubscriberId> {
+    let mut map = HashMap::new();
+    for player in players.iter() {
+        let id = event_log.subscribe(
+            Some(player.id), DeliveryPoll, Vec::new());
+        map.insert(player.id, id);
     }
+    map
+}
 
-    fn make_events(&mut self) {
-        while self.core_event_list.len() != 0 {
-            let event = self.core_event_list.pop().unwrap();
-            event.apply(self);
-            for player in self.players.iter() {
-                let event_list = self.event_lists.get_mut(&player.id);
-                event_list.push(event.to_event());
-            }
+impl Core {
+    pub fn new(overrides: &[~str]) -> Result<~Core, ConfigError> {
+        let rules = Rules::new();
+        let scenario = try!(Scenario::from_layered_config(
+            "conf_core.json", overrides, &rules));
+        Ok(Core::with_rules(&scenario, rules))
+    }
+
+    pub fn from_config(path: &str) -> Result<~Core, ConfigError> {
+        let rules = Rules::new();
+        let scenario = try!(Scenario::from_config(path, &rules));
+        Ok(Core::with_rules(&scenario, rules))
+    }
+
+    // Default rules, see GameSettings::rules for other ones
+    pub fn from_scenario(scenario: &Scenario) -> ~Core {
+        Core::with_rules(scenario, Rules::new())
+    }
+
+    pub fn with_rules(scenario: &Scenario, rules: Rules) -> ~Core {
+        let mut state = CoreState::without_units(scenario, rules);
+        let mut event_log = EventLog::new();
+        let player_subscribers = get_player_subscribers(
+            &mut event_log, &state.players);
+        let mut out = EventOutput::new(None);
+        state.add_units(scenario, &mut out);
+        for event in out.events.move_iter() {
+            event_log.push(event);
+        }
+        ~Core {
+            state: state,
+            event_log: event_log,
+            player_subscribers: player_subscribers,
+            checker: None,
+        }
+    }
+
+    pub fn state<'a>(&'a self) -> &'a CoreState {
+        &self.state
+    }
+
+    pub fn get_player_event(&mut self, player_id: PlayerId) -> Option<Event> {
+        let id = *self.player_subscribers.get(&player_id);
+        self.event_log.next_event(id)
+    }
+
+    // Observer of one player (`view`) or of all players (None).
+    // Subscriber that joins running game gets existing units first.
+    pub fn subscribe(
+        &mut self,
+        view: Option<PlayerId>,
+        delivery: Delivery
+    ) -> SubscriberId {
+        let snapshot = self.state.units_snapshot();
+        self.event_log.subscribe(view, delivery, snapshot)
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriberId) {
+        self.event_log.unsubscribe(id);
+    }
+
+    // Only for subscribers with DeliveryPoll
+    pub fn get_subscriber_event(&mut self, id: SubscriberId) -> Option<Event> {
+        self.event_log.next_event(id)
+    }
+
+    // Debug mode: every following event is also applied to shadow
+    // players` game states that are checked against Core`s units
+    pub fn enable_consistency_check(&mut self) {
+        let mut checker = ConsistencyChecker::new(
+            self.players_count(), self.state.rules.clone());
+        for event in self.state.units_snapshot().iter() {
+            checker.check(&self.state, event);
+        }
+        self.checker = Some(checker);
+    }
+
+    pub fn divergences<'a>(&'a self) -> &'a [Divergence] {
+        match self.checker {
+            Some(ref checker) => checker.divergences(),
+            None => &[],
+        }
+    }
+
+    pub fn winner(&self) -> Option<PlayerId> {
+        self.state.winner()
+    }
+
+    // Sends events of valid command to subscribers
+    pub fn try_command(&mut self, command: Command) -> Result<(), ~str> {
+        let events = {
+            let mut out = EventOutput::new(self.checker.as_mut());
+            try!(self.state.do_command(command, &mut out));
+            out.events
+        };
+        for event in events.move_iter() {
+            self.event_log.push(event);
+        }
+        Ok(())
+    }
+}
+
+// Everything frontend needs to play: implemented by local Core
+// and by net::Client that talks to Core running on a server.
+pub trait CoreInterface {
+    fn map_size(&self) -> Size2<MInt>;
+    fn rules(&self) -> Rules;
+    fn players_count(&self) -> MInt;
+    fn player_kind(&self, player_id: PlayerId) -> PlayerKind;
+
+    // Player whose events get_event returns and who sends commands
+    fn player_id(&self) -> PlayerId;
+
+    // False while other player`s turn is played somewhere else
+    fn is_player_turn(&self) -> MBool;
+
+    fn get_event(&mut self) -> Option<Event>;
+    fn do_command(&mut self, command: Command);
+
+    // Problem that frontend should show to player, returned once
+    fn take_error(&mut self) -> Option<~str> {
+        None
+    }
+}
+
+impl CoreInterface for Core {
+    fn map_size(&self) -> Size2<MInt> {
+        self.state.map_size()
+    }
+
+    fn rules(&self) -> Rules {
+        self.state.rules.clone()
+    }
+
+    fn players_count(&self) -> MInt {
+        self.state.players_count()
+    }
+
+    fn player_kind(&self, player_id: PlayerId) -> PlayerKind {
+        self.state.player_kind(player_id)
+    }
+
+    fn player_id(&self) -> PlayerId {
+        self.state.player_id()
+    }
+
+    fn is_player_turn(&self) -> MBool {
+        true
+    }
+
+    fn get_event(&mut self) -> Option<Event> {
+        let player_id = self.state.player_id();
+        self.get_player_event(player_id)
+    }
+
+    // Frontend can not get the error, invalid command makes no events
+    fn do_command(&mut self, command: Command) {
+        match self.try_command(command) {
+            Ok(()) => {},
+            Err(_) => {},
         }
     }
 }
 
 trait CoreEvent {
-    fn apply(&self, core: &mut Core);
+    fn apply(&self, state: &mut CoreState);
     fn to_event(&self) -> Event;
     // TODO: fn is_visible(&self) -> MBool;
 }
@@ -142,7 +605,11 @@ struct CoreEventMove {
 }
 
 impl CoreEventMove {
-    fn new(_: &Core, unit_id: UnitId, path: Vec<MapPos>) -> ~CoreEventMove {
+    fn new(
+        _: &CoreState,
+        unit_id: UnitId,
+        path: Vec<MapPos>
+    ) -> ~CoreEventMove {
         ~CoreEventMove {
             path: path,
             unit_id: unit_id,
@@ -155,8 +622,8 @@ impl CoreEvent for CoreEventMove {
         EventMove(self.unit_id, self.path.clone())
     }
 
-    fn apply(&self, core: &mut Core) {
-        let unit = core.units.get_mut(&self.unit_id);
+    fn apply(&self, state: &mut CoreState) {
+        let unit = state.units.get_mut(&self.unit_id);
         unit.pos = *self.path.last().unwrap();
     }
 }
@@ -167,9 +634,9 @@ struct CoreEventEndTurn {
 }
 
 impl CoreEventEndTurn {
-    fn new(core: &Core) -> ~CoreEventEndTurn {
-        let PlayerId(old_id) = core.current_player_id;
-        let max_id = core.players.len() as MInt;
+    fn new(state: &CoreState) -> ~CoreEventEndTurn {
+        let PlayerId(old_id) = state.current_player_id;
+        let max_id = state.players.len() as MInt;
         let new_id = if old_id + 1 == max_id { 0 } else { old_id + 1 };
         ~CoreEventEndTurn{old_id: PlayerId(old_id), new_id: PlayerId(new_id)}
     }
@@ -180,12 +647,12 @@ impl CoreEvent for CoreEventEndTurn {
         EventEndTurn(self.old_id, self.new_id)
     }
 
-    fn apply(&self, core: &mut Core) {
+    fn apply(&self, state: &mut CoreState) {
         // core.deselected_any_units();
-        for player in core.players.iter() {
+        for player in state.players.iter() {
             if player.id == self.new_id {
-                if core.current_player_id == self.old_id {
-                    core.current_player_id = player.id;
+                if state.current_player_id == self.old_id {
+                    state.current_player_id = player.id;
                 }
                 return;
             }
@@ -201,19 +668,12 @@ struct CoreEventCreateUnit {
 
 impl CoreEventCreateUnit {
     fn new(
-        core: &Core,
+        state: &CoreState,
         pos: MapPos,
         player_id: PlayerId
     ) -> ~CoreEventCreateUnit {
-        let new_id = match core.units.keys().max_by(|&n| n) {
-            Some(n) => {
-                let UnitId(id) = *n;
-                id + 1
-            },
-            None => 0,
-        };
         ~CoreEventCreateUnit {
-            id: UnitId(new_id),
+            id: UnitId(state.next_unit_id),
             pos: pos,
             player_id: player_id,
         }
@@ -225,12 +685,14 @@ impl CoreEvent for CoreEventCreateUnit {
         EventCreateUnit(self.id, self.pos, self.player_id)
     }
 
-    fn apply(&self, core: &mut Core) {
-        assert!(core.units.find(&self.id).is_none());
-        core.units.insert(self.id, Unit {
+    fn apply(&self, state: &mut CoreState) {
+        assert!(state.units.find(&self.id).is_none());
+        let UnitId(id) = self.id;
+        state.next_unit_id = id + 1;
+        state.units.insert(self.id, Unit {
             id: self.id,
             pos: self.pos,
-            player_id: core.current_player_id,
+            player_id: self.player_id,
         });
     }
 }
@@ -242,7 +704,7 @@ struct CoreEventAttackUnit {
 
 impl CoreEventAttackUnit {
     fn new(
-        _: &Core,
+        _: &CoreState,
         attacker_id: UnitId,
         defender_id: UnitId
     ) -> ~CoreEventAttackUnit {
@@ -258,9 +720,48 @@ impl CoreEvent for CoreEventAttackUnit {
         EventAttackUnit(self.attacker_id, self.defender_id)
     }
 
-    fn apply(&self, core: &mut Core) {
-        assert!(core.units.find(&self.defender_id).is_some());
-        core.units.remove(&self.defender_id);
+    fn apply(&self, state: &mut CoreState) {
+        assert!(state.units.find(&self.defender_id).is_some());
+        state.units.remove(&self.defender_id);
+        state.move_orders.remove(&self.defender_id);
+    }
+}
+
+struct CoreEventMoveOrder {
+    unit_id: UnitId,
+    path: Vec<MapPos>,
+    known_enemies: Vec<UnitId>,
+}
+
+impl CoreEventMoveOrder {
+    // Empty path cancels the order
+    fn new(
+        state: &CoreState,
+        unit_id: UnitId,
+        path: Vec<MapPos>
+    ) -> ~CoreEventMoveOrder {
+        ~CoreEventMoveOrder {
+            unit_id: unit_id,
+            path: path,
+            known_enemies: state.enemies_in_sight(unit_id),
+        }
+    }
+}
+
+impl CoreEvent for CoreEventMoveOrder {
+    fn to_event(&self) -> Event {
+        EventMoveOrder(self.unit_id, self.path.clone())
+    }
+
+    fn apply(&self, state: &mut CoreState) {
+        if self.path.len() == 0 {
+            state.move_orders.remove(&self.unit_id);
+        } else {
+            state.move_orders.insert(self.unit_id, MoveOrder {
+                path: self.path.clone(),
+                known_enemies: self.known_enemies.clone(),
+            });
+        }
     }
 }
 
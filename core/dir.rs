@@ -1,7 +1,7 @@
 // See LICENSE file for copyright and license details.
 
 use cgmath::vector::Vec2;
-use core::types::{MInt, MapPos};
+use types::{MInt, MapPos};
 
 pub enum Dir {
   NorthEast,
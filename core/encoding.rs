@@ -0,0 +1,341 @@
+// See LICENSE file for copyright and license details.
+
+use serialize::json;
+use serialize::json::Json;
+use collections::treemap::TreeMap;
+use cgmath::vector::Vec2;
+use types::{MInt, UnitId, PlayerId, MapPos, Size2};
+use scenario::{Scenario, UnitInfo};
+use rules::Rules;
+use core::{
+    PlayerKind,
+    Command,
+    CommandMove,
+    CommandEndTurn,
+    CommandCreateUnit,
+    CommandAttackUnit,
+    CommandSetMoveOrder,
+    Event,
+    EventMove,
+    EventEndTurn,
+    EventCreateUnit,
+    EventAttackUnit,
+    EventMoveOrder,
+};
+
+// Commands and events are encoded as JSON objects
+// with "type" field, positions are encoded as [x, y].
+
+pub fn int_to_json(n: MInt) -> Json {
+    json::Number(n as f64)
+}
+
+pub fn pos_to_json(pos: MapPos) -> Json {
+    json::List(vec!(int_to_json(pos.x), int_to_json(pos.y))
+        .move_iter().collect())
+}
+
+fn path_to_json(path: &Vec<MapPos>) -> Json {
+    json::List(path.iter().map(|pos| pos_to_json(*pos)).collect())
+}
+
+pub fn new_object(type_name: &str) -> TreeMap<~str, Json> {
+    let mut obj = TreeMap::new();
+    obj.insert(~"type", json::String(type_name.to_owned()));
+    obj
+}
+
+pub fn json_to_int(json: &Json) -> Result<MInt, ~str> {
+    match *json {
+        json::Number(n) if n == (n as MInt) as f64 => Ok(n as MInt),
+        _ => Err(format!("expected integer, found {}", json.to_str())),
+    }
+}
+
+pub fn json_to_pos(json: &Json) -> Result<MapPos, ~str> {
+    match *json {
+        json::List(ref list) if list.len() == 2 => {
+            Ok(Vec2 {
+                x: try!(json_to_int(&list[0])),
+                y: try!(json_to_int(&list[1])),
+            })
+        },
+        _ => Err(format!("expected [x, y], found {}", json.to_str())),
+    }
+}
+
+fn json_to_path(json: &Json) -> Result<Vec<MapPos>, ~str> {
+    match *json {
+        json::List(ref list) => {
+            let mut path = Vec::new();
+            for pos in list.iter() {
+                path.push(try!(json_to_pos(pos)));
+            }
+            Ok(path)
+        },
+        _ => Err(format!("expected path, found {}", json.to_str())),
+    }
+}
+
+// Move goes from the first tile to the last one, so it needs both
+fn json_to_move_path(json: &Json) -> Result<Vec<MapPos>, ~str> {
+    let path = try!(json_to_path(json));
+    if path.len() < 2 {
+        return Err(format!("move path must have at least 2 tiles, \
+            found {}", path.len()));
+    }
+    Ok(path)
+}
+
+pub fn get_object<'a>(
+    json: &'a Json
+) -> Result<&'a TreeMap<~str, Json>, ~str> {
+    match *json {
+        json::Object(ref obj) => Ok(&**obj),
+        _ => Err(format!("expected object, found {}", json.to_str())),
+    }
+}
+
+pub fn get_field<'a>(
+    obj: &'a TreeMap<~str, Json>,
+    name: &str
+) -> Result<&'a Json, ~str> {
+    match obj.find(&name.to_owned()) {
+        Some(value) => Ok(value),
+        None => Err(format!("no field '{}'", name)),
+    }
+}
+
+pub fn get_int(obj: &TreeMap<~str, Json>, name: &str) -> Result<MInt, ~str> {
+    json_to_int(try!(get_field(obj, name)))
+}
+
+pub fn get_str<'a>(
+    obj: &'a TreeMap<~str, Json>,
+    name: &str
+) -> Result<&'a str, ~str> {
+    match *try!(get_field(obj, name)) {
+        json::String(ref s) => Ok(s.as_slice()),
+        ref other => Err(format!("field '{}': expected string, found {}",
+            name, other.to_str())),
+    }
+}
+
+fn get_unit_id(obj: &TreeMap<~str, Json>, name: &str) -> Result<UnitId, ~str> {
+    Ok(UnitId(try!(get_int(obj, name))))
+}
+
+fn get_player_id(
+    obj: &TreeMap<~str, Json>,
+    name: &str
+) -> Result<PlayerId, ~str> {
+    Ok(PlayerId(try!(get_int(obj, name))))
+}
+
+pub fn command_to_json(command: &Command) -> Json {
+    let obj = match *command {
+        CommandMove(UnitId(unit_id), ref path) => {
+            let mut obj = new_object("move");
+            obj.insert(~"unit_id", int_to_json(unit_id));
+            obj.insert(~"path", path_to_json(path));
+            obj
+        },
+        CommandEndTurn => new_object("end_turn"),
+        CommandCreateUnit(pos) => {
+            let mut obj = new_object("create_unit");
+            obj.insert(~"pos", pos_to_json(pos));
+            obj
+        },
+        CommandAttackUnit(UnitId(attacker_id), UnitId(defender_id)) => {
+            let mut obj = new_object("attack");
+            obj.insert(~"attacker_id", int_to_json(attacker_id));
+            obj.insert(~"defender_id", int_to_json(defender_id));
+            obj
+        },
+        CommandSetMoveOrder(UnitId(unit_id), ref path) => {
+            let mut obj = new_object("move_order");
+            obj.insert(~"unit_id", int_to_json(unit_id));
+            obj.insert(~"path", path_to_json(path));
+            obj
+        },
+    };
+    json::Object(~obj)
+}
+
+pub fn json_to_command(json: &Json) -> Result<Command, ~str> {
+    let obj = try!(get_object(json));
+    match try!(get_str(obj, "type")) {
+        "move" => Ok(CommandMove(
+            try!(get_unit_id(obj, "unit_id")),
+            try!(json_to_path(try!(get_field(obj, "path")))),
+        )),
+        "end_turn" => Ok(CommandEndTurn),
+        "create_unit" => Ok(CommandCreateUnit(
+            try!(json_to_pos(try!(get_field(obj, "pos")))),
+        )),
+        "attack" => Ok(CommandAttackUnit(
+            try!(get_unit_id(obj, "attacker_id")),
+            try!(get_unit_id(obj, "defender_id")),
+        )),
+        "move_order" => Ok(CommandSetMoveOrder(
+            try!(get_unit_id(obj, "unit_id")),
+            try!(json_to_path(try!(get_field(obj, "path")))),
+        )),
+        name => Err(format!("unknown command type: '{}'", name)),
+    }
+}
+
+pub fn event_to_json(event: &Event) -> Json {
+    let obj = match *event {
+        EventMove(UnitId(unit_id), ref path) => {
+            let mut obj = new_object("move");
+            obj.insert(~"unit_id", int_to_json(unit_id));
+            obj.insert(~"path", path_to_json(path));
+            obj
+        },
+        EventEndTurn(PlayerId(old_id), PlayerId(new_id)) => {
+            let mut obj = new_object("end_turn");
+            obj.insert(~"old_player_id", int_to_json(old_id));
+            obj.insert(~"new_player_id", int_to_json(new_id));
+            obj
+        },
+        EventCreateUnit(UnitId(unit_id), pos, PlayerId(player_id)) => {
+            let mut obj = new_object("create_unit");
+            obj.insert(~"unit_id", int_to_json(unit_id));
+            obj.insert(~"pos", pos_to_json(pos));
+            obj.insert(~"player_id", int_to_json(player_id));
+            obj
+        },
+        EventAttackUnit(UnitId(attacker_id), UnitId(defender_id)) => {
+            let mut obj = new_object("attack");
+            obj.insert(~"attacker_id", int_to_json(attacker_id));
+            obj.insert(~"defender_id", int_to_json(defender_id));
+            obj
+        },
+        EventMoveOrder(UnitId(unit_id), ref path) => {
+            let mut obj = new_object("move_order");
+            obj.insert(~"unit_id", int_to_json(unit_id));
+            obj.insert(~"path", path_to_json(path));
+            obj
+        },
+    };
+    json::Object(~obj)
+}
+
+pub fn json_to_event(json: &Json) -> Result<Event, ~str> {
+    let obj = try!(get_object(json));
+    match try!(get_str(obj, "type")) {
+        "move" => Ok(EventMove(
+            try!(get_unit_id(obj, "unit_id")),
+            try!(json_to_move_path(try!(get_field(obj, "path")))),
+        )),
+        "end_turn" => Ok(EventEndTurn(
+            try!(get_player_id(obj, "old_player_id")),
+            try!(get_player_id(obj, "new_player_id")),
+        )),
+        "create_unit" => Ok(EventCreateUnit(
+            try!(get_unit_id(obj, "unit_id")),
+            try!(json_to_pos(try!(get_field(obj, "pos")))),
+            try!(get_player_id(obj, "player_id")),
+        )),
+        "attack" => Ok(EventAttackUnit(
+            try!(get_unit_id(obj, "attacker_id")),
+            try!(get_unit_id(obj, "defender_id")),
+        )),
+        "move_order" => Ok(EventMoveOrder(
+            try!(get_unit_id(obj, "unit_id")),
+            try!(json_to_path(try!(get_field(obj, "path")))),
+        )),
+        name => Err(format!("unknown event type: '{}'", name)),
+    }
+}
+
+pub fn rules_to_json(rules: &Rules) -> Json {
+    let mut obj = TreeMap::new();
+    obj.insert(~"move_distance", int_to_json(rules.move_distance));
+    obj.insert(~"attack_distance", int_to_json(rules.attack_distance));
+    obj.insert(~"max_units_per_tile",
+        int_to_json(rules.max_units_per_tile as MInt));
+    json::Object(~obj)
+}
+
+pub fn json_to_rules(json: &Json) -> Result<Rules, ~str> {
+    let obj = try!(get_object(json));
+    let max_units_per_tile = try!(get_int(obj, "max_units_per_tile"));
+    if max_units_per_tile < 1 {
+        return Err(~"max_units_per_tile must be positive");
+    }
+    Ok(Rules {
+        move_distance: try!(get_int(obj, "move_distance")),
+        attack_distance: try!(get_int(obj, "attack_distance")),
+        max_units_per_tile: max_units_per_tile as uint,
+    })
+}
+
+pub fn scenario_to_json(scenario: &Scenario) -> Json {
+    let mut size = TreeMap::new();
+    size.insert(~"w", int_to_json(scenario.map_size.w));
+    size.insert(~"h", int_to_json(scenario.map_size.h));
+    let units: Vec<Json> = scenario.units.iter().map(|unit| {
+        let mut obj = TreeMap::new();
+        obj.insert(~"player_id", int_to_json(unit.player_id));
+        obj.insert(~"x", int_to_json(unit.x));
+        obj.insert(~"y", int_to_json(unit.y));
+        json::Object(~obj)
+    }).collect();
+    let mut obj = TreeMap::new();
+    obj.insert(~"map_size", json::Object(~size));
+    obj.insert(~"players", json::List(scenario.players.iter()
+        .map(|kind| json::String(kind.name())).collect()));
+    obj.insert(~"units", json::List(units));
+    json::Object(~obj)
+}
+
+pub fn get_list<'a>(
+    obj: &'a TreeMap<~str, Json>,
+    name: &str
+) -> Result<&'a [Json], ~str> {
+    match *try!(get_field(obj, name)) {
+        json::List(ref list) => Ok(list.as_slice()),
+        ref other => Err(format!("field '{}': expected list, found {}",
+            name, other.to_str())),
+    }
+}
+
+pub fn json_to_scenario(
+    json: &Json,
+    rules: &Rules
+) -> Result<Scenario, ~str> {
+    let obj = try!(get_object(json));
+    let size = try!(get_object(try!(get_field(obj, "map_size"))));
+    let mut players = Vec::new();
+    for name in try!(get_list(obj, "players")).iter() {
+        match *name {
+            json::String(ref name) => {
+                players.push(PlayerKind::from_str(name.as_slice()));
+            },
+            _ => return Err(format!("bad player: {}", name.to_str())),
+        }
+    }
+    let mut units = Vec::new();
+    for unit in try!(get_list(obj, "units")).iter() {
+        let unit = try!(get_object(unit));
+        units.push(UnitInfo {
+            player_id: try!(get_int(unit, "player_id")),
+            x: try!(get_int(unit, "x")),
+            y: try!(get_int(unit, "y")),
+        });
+    }
+    let scenario = Scenario {
+        map_size: Size2 {
+            w: try!(get_int(size, "w")),
+            h: try!(get_int(size, "h")),
+        },
+        players: players,
+        units: units,
+    };
+    try!(scenario.check(rules));
+    Ok(scenario)
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
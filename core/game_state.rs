@@ -1,24 +1,38 @@
 // See LICENSE file for copyright and license details.
 
 use collections::hashmap::HashMap;
-use core::core::{
+use core::{
     Unit,
+    Command,
+    CommandMove,
+    CommandEndTurn,
+    CommandCreateUnit,
+    CommandAttackUnit,
+    CommandSetMoveOrder,
     Event,
     EventMove,
     EventEndTurn,
     EventCreateUnit,
     EventAttackUnit,
+    EventMoveOrder,
 };
-use core::types::{UnitId, MapPos, MInt};
+use types::{MBool, UnitId, PlayerId, MapPos, MInt, Size2};
+use map::distance;
+use rules::Rules;
 
+#[deriving(Clone)]
 pub struct GameState {
     pub units: HashMap<UnitId, Unit>,
+    pub move_orders: HashMap<UnitId, Vec<MapPos>>, // rest of paths
+    pub rules: Rules,
 }
 
 impl<'a> GameState {
-    pub fn new() -> GameState {
+    pub fn new(rules: Rules) -> GameState {
         GameState {
             units: HashMap::new(),
+            move_orders: HashMap::new(),
+            rules: rules,
         }
     }
 
@@ -32,6 +46,10 @@ impl<'a> GameState {
         units
     }
 
+    pub fn is_full_tile(&self, pos: MapPos) -> MBool {
+        self.units_at(pos).len() >= self.rules.max_units_per_tile
+    }
+
     pub fn apply_event(&mut self, event: &Event) {
         match *event {
             EventMove(id, ref path) => {
@@ -50,8 +68,40 @@ impl<'a> GameState {
             EventAttackUnit(_, defender_id) => {
                 assert!(self.units.find(&defender_id).is_some());
                 self.units.remove(&defender_id);
+                self.move_orders.remove(&defender_id);
             },
+            EventMoveOrder(id, ref path) => {
+                if path.len() == 0 {
+                    self.move_orders.remove(&id);
+                } else {
+                    self.move_orders.insert(id, path.clone());
+                }
+            },
+        }
+    }
+
+    // Returns Some(player_id) when only one player still has units
+    pub fn winner(&self) -> Option<PlayerId> {
+        let mut winner = None;
+        for (_, unit) in self.units.iter() {
+            match winner {
+                Some(player_id) if player_id != unit.player_id => {
+                    return None;
+                },
+                _ => winner = Some(unit.player_id),
+            }
         }
+        winner
+    }
+
+    // Commands from scripts and remote clients can not be trusted
+    pub fn check_command(
+        &self,
+        map_size: Size2<MInt>,
+        player_id: PlayerId,
+        command: &Command
+    ) -> Result<(), ~str> {
+        check_command(&self.units, map_size, &self.rules, player_id, command)
     }
 
     pub fn get_slot_index(&self, unit_id: UnitId, pos: MapPos) -> MInt {
@@ -66,4 +116,107 @@ impl<'a> GameState {
     }
 }
 
+fn units_at_count(units: &HashMap<UnitId, Unit>, pos: MapPos) -> uint {
+    units.iter().filter(|&(_, unit)| unit.pos == pos).count()
+}
+
+fn check_unit(
+    units: &HashMap<UnitId, Unit>,
+    player_id: PlayerId,
+    unit_id: UnitId
+) -> Result<(), ~str> {
+    let UnitId(id) = unit_id;
+    match units.find(&unit_id) {
+        Some(unit) if unit.player_id == player_id => Ok(()),
+        Some(_) => Err(format!("unit {} belongs to other player", id)),
+        None => Err(format!("no unit with id {}", id)),
+    }
+}
+
+fn check_path(
+    units: &HashMap<UnitId, Unit>,
+    map_size: Size2<MInt>,
+    unit_id: UnitId,
+    path: &Vec<MapPos>
+) -> Result<(), ~str> {
+    if path.len() < 2 {
+        return Err(~"path is too short");
+    }
+    if *path.get(0) != units.get(&unit_id).pos {
+        return Err(~"path must start at unit's position");
+    }
+    for (i, pos) in path.iter().enumerate().skip(1) {
+        if pos.x < 0 || pos.y < 0
+            || pos.x >= map_size.w || pos.y >= map_size.h
+        {
+            return Err(~"path leaves the map");
+        }
+        if distance(*path.get(i - 1), *pos) != 1 {
+            return Err(~"path is not continuous");
+        }
+    }
+    Ok(())
+}
+
+// Shared by players` GameState and by Core, which knows all units
+pub fn check_command(
+    units: &HashMap<UnitId, Unit>,
+    map_size: Size2<MInt>,
+    rules: &Rules,
+    player_id: PlayerId,
+    command: &Command
+) -> Result<(), ~str> {
+    let is_inboard = |pos: &MapPos| {
+        pos.x >= 0 && pos.y >= 0
+            && pos.x < map_size.w && pos.y < map_size.h
+    };
+    let is_full_tile = |pos: MapPos| {
+        units_at_count(units, pos) >= rules.max_units_per_tile
+    };
+    match *command {
+        CommandMove(unit_id, ref path) => {
+            try!(check_unit(units, player_id, unit_id));
+            try!(check_path(units, map_size, unit_id, path));
+            if path.len() as MInt - 1 > rules.move_distance {
+                return Err(~"path is too long for one turn");
+            }
+            if is_full_tile(*path.last().unwrap()) {
+                return Err(~"destination tile is full");
+            }
+            Ok(())
+        },
+        // Tiles on the way may change before unit gets there,
+        // so they are checked when order is carried out
+        CommandSetMoveOrder(unit_id, ref path) => {
+            try!(check_unit(units, player_id, unit_id));
+            check_path(units, map_size, unit_id, path)
+        },
+        CommandAttackUnit(attacker_id, defender_id) => {
+            try!(check_unit(units, player_id, attacker_id));
+            let defender = match units.find(&defender_id) {
+                Some(defender) => defender,
+                None => return Err(~"no defender with such id"),
+            };
+            if defender.player_id == player_id {
+                return Err(~"can not attack own unit");
+            }
+            let attacker = units.get(&attacker_id);
+            if distance(attacker.pos, defender.pos) > rules.attack_distance {
+                return Err(~"defender is too far");
+            }
+            Ok(())
+        },
+        CommandCreateUnit(pos) => {
+            if !is_inboard(&pos) {
+                return Err(~"position is out of the map");
+            }
+            if is_full_tile(pos) {
+                return Err(~"tile is full");
+            }
+            Ok(())
+        },
+        CommandEndTurn => Ok(()),
+    }
+}
+
 // vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
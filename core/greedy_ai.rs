@@ -0,0 +1,137 @@
+// See LICENSE file for copyright and license details.
+
+use std::cmp::min;
+use types::{MInt, UnitId, PlayerId, MapPos, Size2};
+use core::{
+    Unit,
+    Command,
+    CommandMove,
+    CommandEndTurn,
+    CommandAttackUnit,
+    Event,
+};
+use game_state::GameState;
+use pathfinder::Pathfinder;
+use ai::{
+    Ai,
+    player_units,
+    simulate_command,
+};
+
+// Attacks closest enemy if it is in range or moves towards it
+pub struct GreedyAi {
+    id: PlayerId,
+    pathfinder: Pathfinder,
+    moved_units: Vec<UnitId>,
+    attacked_units: Vec<UnitId>,
+}
+
+impl GreedyAi {
+    pub fn new(id: PlayerId, map_size: Size2<MInt>) -> GreedyAi {
+        GreedyAi {
+            id: id,
+            pathfinder: Pathfinder::new(map_size),
+            moved_units: Vec::new(),
+            attacked_units: Vec::new(),
+        }
+    }
+
+    // Returns closest enemy and path to it. Flow field is shared
+    // by all units of the player until somebody moves or dies.
+    fn find_target(
+        &mut self,
+        state: &GameState,
+        unit: &Unit
+    ) -> Option<(UnitId, Vec<MapPos>)> {
+        // Sorted, so that the enemy with smaller id wins a tie
+        let mut enemy_ids: Vec<UnitId> = state.units.iter()
+            .filter(|&(_, enemy)| enemy.player_id != self.id)
+            .map(|(id, _)| *id)
+            .collect();
+        enemy_ids.sort();
+        let targets: Vec<MapPos> = enemy_ids.iter()
+            .map(|id| state.units.get(id).pos)
+            .collect();
+        self.pathfinder.fill_flow_field(state, unit, targets.as_slice());
+        match self.pathfinder.get_flow_target(unit.pos) {
+            Some(index) => Some((
+                *enemy_ids.get(index),
+                self.pathfinder.get_flow_path(unit.pos),
+            )),
+            None => None,
+        }
+    }
+
+    // Cuts path to enemy so that unit stops at attack distance
+    fn get_move_path(
+        &self,
+        state: &GameState,
+        path: Vec<MapPos>
+    ) -> Option<Vec<MapPos>> {
+        let rules = &state.rules;
+        let distance = path.len() as MInt - 1;
+        let mut last = min(
+            rules.move_distance, distance - rules.attack_distance);
+        while last > 0 && state.is_full_tile(*path.get(last as uint)) {
+            last -= 1;
+        }
+        if last <= 0 {
+            return None;
+        }
+        let mut path = path;
+        path.truncate(last as uint + 1);
+        Some(path)
+    }
+
+    fn get_command(&mut self, state: &GameState) -> Command {
+        let units = player_units(state, self.id);
+        for unit_id in units.iter() {
+            if self.attacked_units.contains(unit_id) {
+                continue;
+            }
+            let unit = state.units.get(unit_id);
+            // Boxed in unit must not stop the rest of the army
+            let (enemy_id, path) = match self.find_target(state, unit) {
+                Some(target) => target,
+                None => continue,
+            };
+            let distance = path.len() as MInt - 1;
+            if distance <= state.rules.attack_distance {
+                self.attacked_units.push(*unit_id);
+                return CommandAttackUnit(*unit_id, enemy_id);
+            }
+            if self.moved_units.contains(unit_id) {
+                continue;
+            }
+            self.moved_units.push(*unit_id);
+            match self.get_move_path(state, path) {
+                Some(path) => return CommandMove(*unit_id, path),
+                None => {},
+            }
+        }
+        self.moved_units.clear();
+        self.attacked_units.clear();
+        CommandEndTurn
+    }
+}
+
+impl Ai for GreedyAi {
+    fn take_turn(&mut self, state: &GameState, _: &[Event]) -> Vec<Command> {
+        let mut state = state.clone();
+        let mut commands = Vec::new();
+        loop {
+            let command = self.get_command(&state);
+            let is_end_turn = match command {
+                CommandEndTurn => true,
+                _ => false,
+            };
+            simulate_command(&mut state, &command);
+            commands.push(command);
+            if is_end_turn {
+                return commands;
+            }
+        }
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -0,0 +1,103 @@
+// See LICENSE file for copyright and license details.
+
+use std::io::{File, IoResult, IoError};
+use serialize::json;
+use core::Event;
+use types::MBool;
+use encoding::{event_to_json, json_to_event};
+use observer::EventObserver;
+use misc::try_read_file;
+
+// Event log in JSON Lines: one event object per line,
+// same encoding as in network protocol
+
+pub fn event_to_line(event: &Event) -> ~str {
+    event_to_json(event).to_str()
+}
+
+pub fn line_to_event(line: &str) -> Result<Event, ~str> {
+    match json::from_str(line) {
+        Ok(json) => json_to_event(&json),
+        Err(_) => Err(~"line is not valid JSON"),
+    }
+}
+
+pub fn write_events(path: &Path, events: &[Event]) -> IoResult<()> {
+    let mut file = try!(File::create(path));
+    for event in events.iter() {
+        try!(file.write_line(event_to_line(event).as_slice()));
+    }
+    Ok(())
+}
+
+// Empty lines are skipped
+pub fn read_events(path: &Path) -> Result<Vec<Event>, ~str> {
+    let text = match try_read_file(path) {
+        Ok(text) => text,
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    let mut events = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().len() == 0 {
+            continue;
+        }
+        match line_to_event(line) {
+            Ok(event) => events.push(event),
+            Err(e) => {
+                return Err(format!("{}:{}: {}", path.display(), i + 1, e));
+            },
+        }
+    }
+    Ok(events)
+}
+
+// Used as DeliveryCallback subscriber: writes every event as soon
+// as it happens, so the log is complete even if the game crashes.
+// Broken log must not stop the game: the first write error is
+// sent to JsonlWriter::set_errors receiver, later events are dropped.
+pub struct JsonlWriter {
+    file: File,
+    is_stopped: MBool,
+    errors: Option<Sender<IoError>>,
+}
+
+impl JsonlWriter {
+    pub fn new(path: &Path) -> IoResult<JsonlWriter> {
+        Ok(JsonlWriter {
+            file: try!(File::create(path)),
+            is_stopped: false,
+            errors: None,
+        })
+    }
+
+    pub fn set_errors(&mut self, sender: Sender<IoError>) {
+        self.errors = Some(sender);
+    }
+
+    fn write_event(&mut self, event: &Event) -> IoResult<()> {
+        try!(self.file.write_line(event_to_line(event).as_slice()));
+        self.file.flush()
+    }
+}
+
+impl EventObserver for JsonlWriter {
+    fn on_event(&mut self, event: &Event) {
+        if self.is_stopped {
+            return;
+        }
+        match self.write_event(event) {
+            Ok(()) => {},
+            Err(e) => {
+                self.is_stopped = true;
+                match self.errors {
+                    Some(ref sender) => {
+                        let _ = sender.send_opt(e);
+                    },
+                    None => {},
+                }
+            },
+        }
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
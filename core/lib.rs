@@ -0,0 +1,43 @@
+// See LICENSE file for copyright and license details.
+
+#![crate_id = "core#0.1"]
+#![crate_type = "rlib"]
+
+#![deny(non_camel_case_types)]
+#![deny(non_uppercase_statics)]
+#![deny(unnecessary_qualification)]
+#![deny(unnecessary_typecast)]
+
+extern crate serialize;
+extern crate collections;
+extern crate rand;
+extern crate cgmath;
+
+pub mod misc;
+pub mod types;
+pub mod pathfinder;
+pub mod dir;
+pub mod map;
+pub mod game_state;
+pub mod rules;
+pub mod core;
+pub mod conf;
+pub mod ai;
+pub mod greedy_ai;
+pub mod minimax_ai;
+pub mod scenario;
+pub mod runner;
+pub mod replay;
+pub mod encoding;
+pub mod net;
+pub mod server;
+pub mod summary;
+pub mod pbem;
+pub mod spectator;
+pub mod checker;
+pub mod observer;
+pub mod settings;
+pub mod jsonl;
+pub mod locator;
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -0,0 +1,64 @@
+// See LICENSE file for copyright and license details.
+
+use std::os;
+
+// Finds game files in an ordered list of roots: base game, mods
+// and user`s directory. Later roots override earlier ones, so a mod
+// can replace any texture, model, shader or map with its own file.
+pub struct Locator {
+    roots: Vec<Path>,
+}
+
+impl Locator {
+    pub fn new() -> Locator {
+        Locator {
+            roots: Vec::new(),
+        }
+    }
+
+    // Game directory and `data_dir` go first, then `mods` in given
+    // order and then user`s `~/.marauder/data` if there is a home
+    pub fn with_mods(data_dir: &Path, mods: &[Path]) -> Locator {
+        let mut locator = Locator::new();
+        locator.add_root(Path::new("."));
+        locator.add_root(data_dir.clone());
+        for mod_dir in mods.iter() {
+            locator.add_root(mod_dir.clone());
+        }
+        match os::homedir() {
+            Some(dir) => locator.add_root(dir.join(".marauder").join("data")),
+            None => {},
+        }
+        locator
+    }
+
+    pub fn add_root(&mut self, root: Path) {
+        self.roots.push(root);
+    }
+
+    pub fn roots<'a>(&'a self) -> &'a [Path] {
+        self.roots.as_slice()
+    }
+
+    pub fn find(&self, name: &str) -> Result<Path, ~str> {
+        for root in self.roots.iter().rev() {
+            let path = root.join(name);
+            if path.exists() {
+                return Ok(path);
+            }
+        }
+        let roots: Vec<~str> = self.roots.iter()
+            .map(|root| format!("{}", root.display()))
+            .collect();
+        Err(format!("'{}' is not found in: {}",
+            name, roots.connect(", ")))
+    }
+
+    // For functions that take paths as strings
+    pub fn find_str(&self, name: &str) -> Result<~str, ~str> {
+        let path = try!(self.find(name));
+        Ok(format!("{}", path.display()))
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -1,7 +1,22 @@
 // See LICENSE file for copyright and license details.
 
+use std::cmp::max;
+use std::num::abs;
 use cgmath::vector::Vec2;
-use core::types::{Size2, MInt, MapPos};
+use types::{Size2, MInt, MapPos};
+
+// Even rows are shifted to the right, see dir::DIR_TO_POS_DIFF
+fn to_cube(pos: MapPos) -> (MInt, MInt, MInt) {
+    let x = pos.x - (pos.y + (pos.y & 1)) / 2;
+    let z = pos.y;
+    (x, -x - z, z)
+}
+
+pub fn distance(from: MapPos, to: MapPos) -> MInt {
+    let (ax, ay, az) = to_cube(from);
+    let (bx, by, bz) = to_cube(to);
+    max(abs(ax - bx), max(abs(ay - by), abs(az - bz)))
+}
 
 pub struct MapPosIter {
     cursor: MapPos,
@@ -0,0 +1,194 @@
+// See LICENSE file for copyright and license details.
+
+use std::cmp::min;
+use types::{MInt, UnitId, PlayerId, Size2};
+use core::{
+    Command,
+    CommandMove,
+    CommandEndTurn,
+    CommandAttackUnit,
+    Event,
+};
+use game_state::GameState;
+use pathfinder::Pathfinder;
+use map::distance;
+use ai::{
+    Ai,
+    player_units,
+    simulate_command,
+};
+
+static UNIT_VALUE: MInt = 100;
+
+// Two-ply minimax: every candidate command is rated by the worst
+// position that opponent can leave us in with one reply attack.
+pub struct MinimaxAi {
+    id: PlayerId,
+    pathfinder: Pathfinder,
+    moved_units: Vec<UnitId>,
+    attacked_units: Vec<UnitId>,
+}
+
+impl MinimaxAi {
+    pub fn new(id: PlayerId, map_size: Size2<MInt>) -> MinimaxAi {
+        MinimaxAi {
+            id: id,
+            pathfinder: Pathfinder::new(map_size),
+            moved_units: Vec::new(),
+            attacked_units: Vec::new(),
+        }
+    }
+
+    fn enemy_units(&self, state: &GameState) -> Vec<UnitId> {
+        let mut ids = Vec::new();
+        for (id, unit) in state.units.iter() {
+            if unit.player_id != self.id {
+                ids.push(*id);
+            }
+        }
+        ids.sort();
+        ids
+    }
+
+    // Material balance minus distances from own units to closest enemies
+    fn evaluate(&self, state: &GameState) -> MInt {
+        let own_units = player_units(state, self.id);
+        let enemy_units = self.enemy_units(state);
+        let units_diff = own_units.len() as MInt - enemy_units.len() as MInt;
+        let mut value = units_diff * UNIT_VALUE;
+        for own_id in own_units.iter() {
+            let own_pos = state.units.get(own_id).pos;
+            let mut min_dist = None;
+            for enemy_id in enemy_units.iter() {
+                let enemy_pos = state.units.get(enemy_id).pos;
+                let dist = distance(own_pos, enemy_pos);
+                min_dist = Some(match min_dist {
+                    Some(min_dist) => min(min_dist, dist),
+                    None => dist,
+                });
+            }
+            value -= min_dist.unwrap_or(0);
+        }
+        value
+    }
+
+    // Opponent`s reply is an attack on any of our units in its reach
+    fn min_reply_value(&self, state: &GameState) -> MInt {
+        let reach = state.rules.sight();
+        let own_units = player_units(state, self.id);
+        let mut value = self.evaluate(state);
+        for enemy_id in self.enemy_units(state).iter() {
+            let enemy_pos = state.units.get(enemy_id).pos;
+            for own_id in own_units.iter() {
+                let own_pos = state.units.get(own_id).pos;
+                if distance(enemy_pos, own_pos) > reach {
+                    continue;
+                }
+                let mut next_state = state.clone();
+                let reply = CommandAttackUnit(*enemy_id, *own_id);
+                simulate_command(&mut next_state, &reply);
+                value = min(value, self.evaluate(&next_state));
+            }
+        }
+        value
+    }
+
+    fn get_attack_commands(
+        &self,
+        state: &GameState,
+        unit_id: UnitId
+    ) -> Vec<Command> {
+        let mut commands = Vec::new();
+        let pos = state.units.get(&unit_id).pos;
+        for enemy_id in self.enemy_units(state).iter() {
+            let enemy_pos = state.units.get(enemy_id).pos;
+            if distance(pos, enemy_pos) <= state.rules.attack_distance {
+                commands.push(CommandAttackUnit(unit_id, *enemy_id));
+            }
+        }
+        commands
+    }
+
+    fn get_move_commands(
+        &mut self,
+        state: &GameState,
+        unit_id: UnitId
+    ) -> Vec<Command> {
+        let mut commands = Vec::new();
+        let unit = state.units.get(&unit_id);
+        let move_distance = state.rules.move_distance;
+        self.pathfinder.fill_map_within(state, unit, move_distance);
+        for tile in self.pathfinder.get_reachable(move_distance).iter() {
+            if tile.pos != unit.pos {
+                let path = self.pathfinder.get_path(tile.pos);
+                commands.push(CommandMove(unit_id, path));
+            }
+        }
+        commands
+    }
+
+    fn get_commands(&mut self, state: &GameState) -> Vec<Command> {
+        let mut commands = Vec::new();
+        for unit_id in player_units(state, self.id).iter() {
+            if !self.attacked_units.contains(unit_id) {
+                commands.push_all_move(
+                    self.get_attack_commands(state, *unit_id));
+            }
+            if !self.moved_units.contains(unit_id) {
+                commands.push_all_move(
+                    self.get_move_commands(state, *unit_id));
+            }
+        }
+        commands
+    }
+
+    fn mark_unit(&mut self, command: &Command) {
+        match *command {
+            CommandMove(unit_id, _) => {
+                self.moved_units.push(unit_id);
+            },
+            CommandAttackUnit(unit_id, _) => {
+                self.moved_units.push(unit_id);
+                self.attacked_units.push(unit_id);
+            },
+            _ => {},
+        }
+    }
+
+    fn get_best_command(&mut self, state: &GameState) -> Option<Command> {
+        let mut best_value = self.min_reply_value(state);
+        let mut best_command = None;
+        for command in self.get_commands(state).move_iter() {
+            let mut next_state = state.clone();
+            simulate_command(&mut next_state, &command);
+            let value = self.min_reply_value(&next_state);
+            if value > best_value {
+                best_value = value;
+                best_command = Some(command);
+            }
+        }
+        best_command
+    }
+}
+
+impl Ai for MinimaxAi {
+    fn take_turn(&mut self, state: &GameState, _: &[Event]) -> Vec<Command> {
+        let mut state = state.clone();
+        let mut commands = Vec::new();
+        loop {
+            let command = match self.get_best_command(&state) {
+                Some(command) => command,
+                None => break,
+            };
+            self.mark_unit(&command);
+            simulate_command(&mut state, &command);
+            commands.push(command);
+        }
+        self.moved_units.clear();
+        self.attacked_units.clear();
+        commands.push(CommandEndTurn);
+        commands
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
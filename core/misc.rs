@@ -3,7 +3,7 @@
 use std::f32::consts::PI;
 use std::io::File;
 use std::str::from_utf8_owned;
-use visualizer::types::MFloat;
+use types::MFloat;
 
 pub fn deg_to_rad(n: MFloat) -> MFloat {
     n * PI / 180.0
@@ -13,20 +13,24 @@ pub fn rad_to_deg(n: MFloat) -> MFloat {
     (n * 180.0) / PI
 }
 
-pub fn read_file(path: &Path) -> ~str {
+pub fn try_read_file(path: &Path) -> Result<~str, ~str> {
     if !path.exists() {
-        fail!("Path does not exists: {}", path.display());
+        return Err(~"file does not exist");
     }
-    let shader = match File::open(path).map(|mut v| v.read_to_end()) {
-        Ok(txt) => from_utf8_owned(match txt {
-            Ok(txt) => txt,
-            Err(_) => fail!("Can not read file {}", path.display()),
-        }),
-        Err(_) => fail!("Can not read file {}", path.display()),
+    let bytes = match File::open(path).and_then(|mut f| f.read_to_end()) {
+        Ok(bytes) => bytes,
+        Err(e) => return Err(format!("can not read file: {}", e)),
     };
-    match shader {
-        Some(shader) => shader,
-        None => fail!("Can not read file {}", path.display()),
+    match from_utf8_owned(bytes) {
+        Some(text) => Ok(text),
+        None => Err(~"file is not valid UTF-8"),
+    }
+}
+
+pub fn read_file(path: &Path) -> ~str {
+    match try_read_file(path) {
+        Ok(text) => text,
+        Err(e) => fail!("{}: {}", path.display(), e),
     }
 }
 
@@ -1,12 +0,0 @@
-// See LICENSE file for copyright and license details.
-
-pub mod misc;
-pub mod types;
-pub mod pathfinder;
-pub mod dir;
-pub mod map;
-pub mod game_state;
-pub mod core;
-pub mod conf;
-
-// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
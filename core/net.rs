@@ -0,0 +1,435 @@
+// See LICENSE file for copyright and license details.
+
+use std::comm::{Empty, Disconnected};
+use std::io::BufferedReader;
+use std::io::net::ip::SocketAddr;
+use std::io::net::tcp::TcpStream;
+use serialize::json;
+use serialize::json::Json;
+use collections::treemap::TreeMap;
+use types::{MBool, MInt, PlayerId, Size2};
+use rules::Rules;
+use core::{
+    CoreInterface,
+    Command,
+    Event,
+    EventEndTurn,
+    PlayerKind,
+    PlayerHuman,
+};
+use encoding::{
+    int_to_json,
+    new_object,
+    get_object,
+    get_field,
+    get_int,
+    get_str,
+    command_to_json,
+    json_to_command,
+    event_to_json,
+    json_to_event,
+    rules_to_json,
+    json_to_rules,
+};
+
+// Must be changed on every incompatible change of the messages below
+pub static PROTOCOL_VERSION: MInt = 2;
+
+// Every message is one line of JSON:
+//
+// client: {"type": "hello", "version": 2, "player_id": 0 or null}
+// server: {"type": "welcome", "version": 2, "player_id": 0,
+//          "players_count": 2, "map_size": {"w": 9, "h": 6},
+//          "rules": {"move_distance": 3, "attack_distance": 1,
+//                    "max_units_per_tile": 6},
+//          "events_count": 4}
+// client: {"type": "command", "command": {...}}
+// server: {"type": "event", "event": {...}}
+// server: {"type": "error", "message": "..."}
+// server: {"type": "game_over", "winner": 0 or null}
+// client: {"type": "bye"}
+//
+// "welcome" is followed by "events_count" events: everything player
+// has received since the start of the game, so reconnected client
+// rebuilds its state from scratch.
+
+pub enum ClientMessage {
+    ClientHello(MInt, Option<PlayerId>), // version, requested player
+    ClientCommand(Command),
+    ClientBye,
+}
+
+pub struct Welcome {
+    pub player_id: PlayerId,
+    pub players_count: MInt,
+    pub map_size: Size2<MInt>,
+    pub rules: Rules,
+    pub events_count: MInt,
+}
+
+pub enum ServerMessage {
+    ServerWelcome(Welcome),
+    ServerEvent(Event),
+    ServerError(~str),
+    ServerGameOver(Option<PlayerId>), // winner
+}
+
+fn player_id_to_json(player_id: Option<PlayerId>) -> Json {
+    match player_id {
+        Some(PlayerId(id)) => int_to_json(id),
+        None => json::Null,
+    }
+}
+
+fn json_to_player_id(json: &Json) -> Result<Option<PlayerId>, ~str> {
+    match *json {
+        json::Null => Ok(None),
+        json::Number(n) => Ok(Some(PlayerId(n as MInt))),
+        _ => Err(format!("expected player id, found {}", json.to_str())),
+    }
+}
+
+fn parse_line(line: &str) -> Result<Json, ~str> {
+    match json::from_str(line.trim()) {
+        Ok(json) => Ok(json),
+        Err(_) => Err(format!("malformed message: '{}'", line.trim())),
+    }
+}
+
+pub fn client_message_to_str(message: &ClientMessage) -> ~str {
+    let obj = match *message {
+        ClientHello(version, player_id) => {
+            let mut obj = new_object("hello");
+            obj.insert(~"version", int_to_json(version));
+            obj.insert(~"player_id", player_id_to_json(player_id));
+            obj
+        },
+        ClientCommand(ref command) => {
+            let mut obj = new_object("command");
+            obj.insert(~"command", command_to_json(command));
+            obj
+        },
+        ClientBye => new_object("bye"),
+    };
+    json::Object(~obj).to_str()
+}
+
+pub fn str_to_client_message(line: &str) -> Result<ClientMessage, ~str> {
+    let json = try!(parse_line(line));
+    let obj = try!(get_object(&json));
+    match try!(get_str(obj, "type")) {
+        "hello" => Ok(ClientHello(
+            try!(get_int(obj, "version")),
+            try!(json_to_player_id(try!(get_field(obj, "player_id")))),
+        )),
+        "command" => Ok(ClientCommand(
+            try!(json_to_command(try!(get_field(obj, "command")))),
+        )),
+        "bye" => Ok(ClientBye),
+        name => Err(format!("unknown message type: '{}'", name)),
+    }
+}
+
+pub fn server_message_to_str(message: &ServerMessage) -> ~str {
+    let obj = match *message {
+        ServerWelcome(ref welcome) => {
+            let mut size = TreeMap::new();
+            size.insert(~"w", int_to_json(welcome.map_size.w));
+            size.insert(~"h", int_to_json(welcome.map_size.h));
+            let mut obj = new_object("welcome");
+            obj.insert(~"version", int_to_json(PROTOCOL_VERSION));
+            obj.insert(~"player_id", player_id_to_json(
+                Some(welcome.player_id)));
+            obj.insert(~"players_count", int_to_json(welcome.players_count));
+            obj.insert(~"map_size", json::Object(~size));
+            obj.insert(~"rules", rules_to_json(&welcome.rules));
+            obj.insert(~"events_count", int_to_json(welcome.events_count));
+            obj
+        },
+        ServerEvent(ref event) => {
+            let mut obj = new_object("event");
+            obj.insert(~"event", event_to_json(event));
+            obj
+        },
+        ServerError(ref message) => {
+            let mut obj = new_object("error");
+            obj.insert(~"message", json::String(message.clone()));
+            obj
+        },
+        ServerGameOver(winner) => {
+            let mut obj = new_object("game_over");
+            obj.insert(~"winner", player_id_to_json(winner));
+            obj
+        },
+    };
+    json::Object(~obj).to_str()
+}
+
+fn json_to_welcome(json: &Json) -> Result<Welcome, ~str> {
+    let obj = try!(get_object(json));
+    let version = try!(get_int(obj, "version"));
+    if version != PROTOCOL_VERSION {
+        return Err(format!("protocol version mismatch: server uses {}, \
+            client uses {}", version, PROTOCOL_VERSION));
+    }
+    let player_id = try!(json_to_player_id(try!(get_field(obj, "player_id"))));
+    let size = try!(get_object(try!(get_field(obj, "map_size"))));
+    Ok(Welcome {
+        player_id: match player_id {
+            Some(player_id) => player_id,
+            None => return Err(~"no player id in welcome"),
+        },
+        players_count: try!(get_int(obj, "players_count")),
+        map_size: Size2 {
+            w: try!(get_int(size, "w")),
+            h: try!(get_int(size, "h")),
+        },
+        rules: try!(json_to_rules(try!(get_field(obj, "rules")))),
+        events_count: try!(get_int(obj, "events_count")),
+    })
+}
+
+pub fn str_to_server_message(line: &str) -> Result<ServerMessage, ~str> {
+    let json = try!(parse_line(line));
+    let obj = try!(get_object(&json));
+    match try!(get_str(obj, "type")) {
+        "welcome" => Ok(ServerWelcome(try!(json_to_welcome(&json)))),
+        "event" => Ok(ServerEvent(
+            try!(json_to_event(try!(get_field(obj, "event")))),
+        )),
+        "error" => Ok(ServerError(try!(get_str(obj, "message")).to_owned())),
+        "game_over" => Ok(ServerGameOver(
+            try!(json_to_player_id(try!(get_field(obj, "winner")))),
+        )),
+        name => Err(format!("unknown message type: '{}'", name)),
+    }
+}
+
+fn read_message(
+    reader: &mut BufferedReader<TcpStream>
+) -> Result<ServerMessage, ~str> {
+    match reader.read_line() {
+        Ok(line) => str_to_server_message(line.as_slice()),
+        Err(e) => Err(format!("can not read from server: {}", e)),
+    }
+}
+
+// Runs in its own task so that visualizer never blocks on network.
+// Stops after "game_over" or when server closes connection.
+fn receive_messages(
+    mut reader: BufferedReader<TcpStream>,
+    sender: Sender<ServerMessage>
+) {
+    loop {
+        let line = match reader.read_line() {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let message = match str_to_server_message(line.as_slice()) {
+            Ok(message) => message,
+            Err(e) => ServerError(e),
+        };
+        let is_game_over = match message {
+            ServerGameOver(_) => true,
+            _ => false,
+        };
+        let _ = sender.send_opt(message);
+        if is_game_over {
+            break;
+        }
+    }
+}
+
+// Remote Core: sends commands to server and receives events of one player
+pub struct Client {
+    stream: TcpStream,
+    receiver: Receiver<ServerMessage>,
+    player_id: PlayerId,
+    current_player_id: PlayerId,
+    players_count: MInt,
+    map_size: Size2<MInt>,
+    rules: Rules,
+    events: Vec<Event>, // received during handshake
+    errors: Vec<~str>,
+    is_connected: MBool,
+    game_over: Option<Option<PlayerId>>, // Some(winner) when game is over
+}
+
+impl Client {
+    // Pass Some(player_id) to take player`s slot back after disconnect
+    pub fn connect(
+        addr: SocketAddr,
+        player_id: Option<PlayerId>
+    ) -> Result<Client, ~str> {
+        let mut stream = match TcpStream::connect(addr) {
+            Ok(stream) => stream,
+            Err(e) => return Err(format!("{}: {}", addr, e)),
+        };
+        let hello = ClientHello(PROTOCOL_VERSION, player_id);
+        let hello = client_message_to_str(&hello);
+        match stream.write_line(hello.as_slice()) {
+            Ok(()) => {},
+            Err(e) => return Err(format!("{}: {}", addr, e)),
+        }
+        let mut reader = BufferedReader::new(stream.clone());
+        let welcome = match try!(read_message(&mut reader)) {
+            ServerWelcome(welcome) => welcome,
+            ServerError(message) => return Err(message),
+            _ => return Err(~"welcome expected"),
+        };
+        let mut events = Vec::new();
+        for _ in range(0, welcome.events_count) {
+            match try!(read_message(&mut reader)) {
+                ServerEvent(event) => events.push(event),
+                _ => return Err(~"event expected"),
+            }
+        }
+        let (sender, receiver) = channel();
+        spawn(proc() receive_messages(reader, sender));
+        Ok(Client {
+            stream: stream,
+            receiver: receiver,
+            player_id: welcome.player_id,
+            current_player_id: PlayerId(0),
+            players_count: welcome.players_count,
+            map_size: welcome.map_size,
+            rules: welcome.rules,
+            events: events,
+            errors: Vec::new(),
+            is_connected: true,
+            game_over: None,
+        })
+    }
+
+    pub fn is_connected(&self) -> MBool {
+        self.is_connected
+    }
+
+    pub fn is_game_over(&self) -> MBool {
+        self.game_over.is_some()
+    }
+
+    pub fn winner(&self) -> Option<PlayerId> {
+        match self.game_over {
+            Some(winner) => winner,
+            None => None,
+        }
+    }
+
+    // Reported once, frontend sees it with take_error
+    fn disconnect(&mut self) {
+        if self.is_connected {
+            self.is_connected = false;
+            self.errors.push(~"disconnected from server");
+        }
+    }
+
+    fn on_event(&mut self, event: Event) -> Event {
+        match event {
+            EventEndTurn(_, new_id) => self.current_player_id = new_id,
+            _ => {},
+        }
+        event
+    }
+
+    fn on_message(&mut self, message: ServerMessage) -> Option<Event> {
+        match message {
+            ServerEvent(event) => return Some(self.on_event(event)),
+            ServerError(message) => self.errors.push(message),
+            ServerGameOver(winner) => self.game_over = Some(winner),
+            ServerWelcome(_) => self.errors.push(~"unexpected welcome"),
+        }
+        None
+    }
+
+    // Blocks until next event, returns None when game is over
+    // or connection is lost
+    pub fn wait_event(&mut self) -> Option<Event> {
+        match self.events.shift() {
+            Some(event) => return Some(self.on_event(event)),
+            None => {},
+        }
+        while self.is_connected && self.game_over.is_none() {
+            match self.receiver.recv_opt() {
+                Some(message) => match self.on_message(message) {
+                    Some(event) => return Some(event),
+                    None => {},
+                },
+                None => self.disconnect(),
+            }
+        }
+        None
+    }
+}
+
+impl CoreInterface for Client {
+    fn map_size(&self) -> Size2<MInt> {
+        self.map_size
+    }
+
+    fn rules(&self) -> Rules {
+        self.rules.clone()
+    }
+
+    fn players_count(&self) -> MInt {
+        self.players_count
+    }
+
+    // Other players are controlled by their own clients or by server
+    fn player_kind(&self, _: PlayerId) -> PlayerKind {
+        PlayerHuman
+    }
+
+    fn player_id(&self) -> PlayerId {
+        self.player_id
+    }
+
+    fn is_player_turn(&self) -> MBool {
+        self.is_connected && self.current_player_id == self.player_id
+    }
+
+    fn get_event(&mut self) -> Option<Event> {
+        match self.events.shift() {
+            Some(event) => return Some(self.on_event(event)),
+            None => {},
+        }
+        loop {
+            match self.receiver.try_recv() {
+                Ok(message) => match self.on_message(message) {
+                    Some(event) => return Some(event),
+                    None => {},
+                },
+                Err(Empty) => return None,
+                Err(Disconnected) => {
+                    if self.game_over.is_none() {
+                        self.disconnect();
+                    }
+                    return None;
+                },
+            }
+        }
+    }
+
+    fn do_command(&mut self, command: Command) {
+        let message = client_message_to_str(&ClientCommand(command));
+        match self.stream.write_line(message.as_slice()) {
+            Ok(()) => {},
+            Err(_) => self.disconnect(),
+        }
+    }
+
+    // Server rejects bad commands with an error instead of event
+    fn take_error(&mut self) -> Option<~str> {
+        self.errors.shift()
+    }
+}
+
+impl Drop for Client {
+    // Server closes its side after "bye", that stops receiving task
+    fn drop(&mut self) {
+        let bye = client_message_to_str(&ClientBye);
+        let _ = self.stream.write_line(bye.as_slice());
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -0,0 +1,176 @@
+// See LICENSE file for copyright and license details.
+
+use collections::hashmap::HashMap;
+use collections::ringbuf::RingBuf;
+use collections::deque::Deque;
+use types::{MBool, MInt, PlayerId, SubscriberId};
+use core::Event;
+
+// Called by Core for every event right after it is applied
+pub trait EventObserver {
+    fn on_event(&mut self, event: &Event);
+}
+
+pub enum Delivery {
+    DeliveryPoll, // events are taken with Core::get_subscriber_event
+    DeliveryChannel(Sender<Event>),
+    DeliveryCallback(~EventObserver),
+}
+
+struct Subscriber {
+    view: Option<PlayerId>, // None means events of all players
+    delivery: Delivery,
+    snapshot: Vec<Event>, // given before any event of the stream
+    cursor: uint, // index of next event in the whole stream
+}
+
+// Core has no fog of war yet, so every player sees every event
+fn is_visible(_: Option<PlayerId>, _: &Event) -> MBool {
+    true
+}
+
+// One stream of events shared by all subscribers. Every subscriber
+// has its own cursor, so AI, frontend, network and logging read it
+// independently. Event is dropped when all polling subscribers
+// have passed it.
+pub struct EventLog {
+    events: RingBuf<Event>,
+    first_index: uint, // index of events.front() in the whole stream
+    subscribers: HashMap<SubscriberId, Subscriber>,
+    next_subscriber_id: MInt,
+}
+
+impl EventLog {
+    pub fn new() -> EventLog {
+        EventLog {
+            events: RingBuf::new(),
+            first_index: 0,
+            subscribers: HashMap::new(),
+            next_subscriber_id: 0,
+        }
+    }
+
+    fn end_index(&self) -> uint {
+        self.first_index + self.events.len()
+    }
+
+    // Snapshot is delivered at once to channels and callbacks
+    // and is returned first to polling subscribers
+    pub fn subscribe(
+        &mut self,
+        view: Option<PlayerId>,
+        delivery: Delivery,
+        snapshot: Vec<Event>
+    ) -> SubscriberId {
+        let id = SubscriberId(self.next_subscriber_id);
+        self.next_subscriber_id += 1;
+        let mut subscriber = Subscriber {
+            view: view,
+            delivery: delivery,
+            snapshot: snapshot,
+            cursor: self.end_index(),
+        };
+        match subscriber.delivery {
+            DeliveryPoll => {},
+            _ => {
+                for event in subscriber.snapshot.iter() {
+                    deliver(&mut subscriber.delivery, event);
+                }
+                subscriber.snapshot.clear();
+            },
+        }
+        self.subscribers.insert(id, subscriber);
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriberId) {
+        self.subscribers.remove(&id);
+        self.drop_read_events();
+    }
+
+    pub fn push(&mut self, event: Event) {
+        let mut closed_ids = Vec::new();
+        let mut has_pollers = false;
+        for (id, subscriber) in self.subscribers.mut_iter() {
+            match subscriber.delivery {
+                DeliveryPoll => {
+                    has_pollers = true;
+                    continue;
+                },
+                _ => {},
+            }
+            if !is_visible(subscriber.view, &event) {
+                continue;
+            }
+            if !deliver(&mut subscriber.delivery, &event) {
+                closed_ids.push(*id);
+            }
+        }
+        for id in closed_ids.iter() {
+            self.subscribers.remove(id);
+        }
+        if has_pollers {
+            self.events.push_back(event);
+        } else {
+            self.first_index += 1;
+        }
+    }
+
+    // Fails if subscriber does not poll
+    pub fn next_event(&mut self, id: SubscriberId) -> Option<Event> {
+        let event = {
+            let first_index = self.first_index;
+            let end_index = first_index + self.events.len();
+            let events = &self.events;
+            let subscriber = self.subscribers.get_mut(&id);
+            match subscriber.delivery {
+                DeliveryPoll => {},
+                _ => fail!("Subscriber does not poll for events"),
+            }
+            if subscriber.snapshot.len() != 0 {
+                return subscriber.snapshot.shift();
+            }
+            let mut event = None;
+            while event.is_none() && subscriber.cursor < end_index {
+                let e = events.get(subscriber.cursor - first_index);
+                if is_visible(subscriber.view, e) {
+                    event = Some(e.clone());
+                }
+                subscriber.cursor += 1;
+            }
+            event
+        };
+        self.drop_read_events();
+        event
+    }
+
+    fn drop_read_events(&mut self) {
+        let mut min_cursor = self.end_index();
+        for (_, subscriber) in self.subscribers.iter() {
+            match subscriber.delivery {
+                DeliveryPoll if subscriber.cursor < min_cursor => {
+                    min_cursor = subscriber.cursor;
+                },
+                _ => {},
+            }
+        }
+        while self.first_index < min_cursor {
+            self.events.pop_front();
+            self.first_index += 1;
+        }
+    }
+}
+
+// Returns false if channel`s receiver is gone
+fn deliver(delivery: &mut Delivery, event: &Event) -> MBool {
+    match *delivery {
+        DeliveryPoll => true,
+        DeliveryChannel(ref sender) => sender.send_opt(event.clone()).is_ok(),
+        DeliveryCallback(ref mut observer) => {
+            observer.on_event(event);
+            true
+        },
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
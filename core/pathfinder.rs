@@ -1,24 +1,110 @@
 // See LICENSE file for copyright and license details.
 
-use core::types::{MBool, MInt, MapPos, Size2};
-use core::core::Unit;
-use core::game_state::GameState;
-use core::dir::Dir;
+use std::hash::hash;
+use std::u32;
+use collections::hashmap::HashMap;
+use collections::priority_queue::PriorityQueue;
+use types::{MBool, MInt, UnitId, PlayerId, MapPos, Size2};
+use core::Unit;
+use game_state::GameState;
+use dir::Dir;
+use map::distance;
+
+// Price of one step between neighbour tiles, must be positive.
+// None means that unit can not enter `to` tile at all.
+pub trait MoveCost {
+    fn move_cost(
+        &self,
+        state: &GameState,
+        unit: &Unit,
+        from: MapPos,
+        to: MapPos
+    ) -> Option<MInt>;
+
+    // Lower bound of any step`s cost, used by A* heuristic
+    fn min_move_cost(&self) -> MInt;
+}
+
+// There is no terrain yet: every step costs the same for every unit
+pub struct PlainMoveCost;
+
+impl MoveCost for PlainMoveCost {
+    fn move_cost(&self, _: &GameState, _: &Unit, _: MapPos, _: MapPos)
+        -> Option<MInt>
+    {
+        Some(1)
+    }
+
+    fn min_move_cost(&self) -> MInt {
+        1
+    }
+}
+
+fn is_enemy_at(state: &GameState, unit: &Unit, pos: MapPos) -> MBool {
+    state.units_at(pos).iter().any(|u| u.player_id != unit.player_id)
+}
+
+// Same as TileUnits::is_near_enemy, but without collected tile units
+fn is_near_enemy(state: &GameState, unit: &Unit, pos: MapPos) -> MBool {
+    range(0 as MInt, 6).any(|i| {
+        let neighbour_pos = Dir::get_neighbour_pos(pos, Dir::from_int(i));
+        is_enemy_at(state, unit, neighbour_pos)
+    })
+}
+
+// Order independent hash of units` ids, positions and owners:
+// move costs depend only on them, so a filled map stays valid
+// until one of these changes
+fn units_hash(state: &GameState) -> u64 {
+    let mut units_hash = 0;
+    for (_, unit) in state.units.iter() {
+        let UnitId(id) = unit.id;
+        let PlayerId(player_id) = unit.player_id;
+        units_hash ^= hash(&(id, unit.pos.x, unit.pos.y, player_id));
+    }
+    units_hash
+}
+
+pub struct Path {
+    pub positions: Vec<MapPos>,
+    pub cost: MInt,
+}
+
+pub struct ReachableTile {
+    pub pos: MapPos,
+    pub cost: MInt,
+}
 
 struct Tile {
     cost: MInt,
     parent: Option<Dir>,
+    can_stop: MBool, // false for enemy and full tiles
+    source: uint, // index of flow field`s target, 0 for other searches
+    generation: u32, // tile is stale if it differs from map`s one
 }
 
+// Instead of cleaning every tile before a search, map`s generation
+// is increased and tiles are cleaned when search visits them.
+// Visited tiles are also listed, so the result of a short search
+// can be read without a scan of the whole map.
 struct Map {
     size: Size2<MInt>,
     tiles: Vec<Tile>,
+    generation: u32,
+    visited: Vec<MapPos>,
 }
 
 fn max_cost() -> MInt {
     30000
 }
 
+fn clean_tile(tile: &mut Tile) {
+    tile.cost = max_cost();
+    tile.parent = None;
+    tile.can_stop = false;
+    tile.source = 0;
+}
+
 impl<'a> Map {
     fn tile_mut(&'a mut self, pos: MapPos) -> &'a mut Tile {
         self.tiles.get_mut((pos.x + pos.y * self.size.w) as uint)
@@ -33,19 +119,132 @@ impl<'a> Map {
         let y = pos.y;
         x >= 0 && y >= 0 && x < self.size.w && y < self.size.h
     }
+
+    fn is_visited(&self, pos: MapPos) -> MBool {
+        self.tile(pos).generation == self.generation
+    }
+
+    fn cost(&self, pos: MapPos) -> MInt {
+        if self.is_visited(pos) {
+            self.tile(pos).cost
+        } else {
+            max_cost()
+        }
+    }
+
+    // Cleans stale tile before returning it
+    fn visit(&'a mut self, pos: MapPos) -> &'a mut Tile {
+        let generation = self.generation;
+        let tile = self.tiles.get_mut((pos.x + pos.y * self.size.w) as uint);
+        if tile.generation != generation {
+            clean_tile(tile);
+            tile.generation = generation;
+            self.visited.push(pos);
+        }
+        tile
+    }
+
+    fn start_new_generation(&mut self) {
+        if self.generation == u32::MAX {
+            for tile in self.tiles.mut_iter() {
+                clean_tile(tile);
+                tile.generation = 0;
+            }
+            self.generation = 0;
+        }
+        self.generation += 1;
+        self.visited.clear();
+    }
 }
 
+// Units on a tile, collected once per search
+// instead of a scan of all units for every step
+struct TileUnits {
+    count: uint,
+    has_enemy: MBool,
+    is_near_enemy: MBool,
+}
+
+impl TileUnits {
+    fn new() -> TileUnits {
+        TileUnits {
+            count: 0,
+            has_enemy: false,
+            is_near_enemy: false,
+        }
+    }
+}
+
+// Queue entry. Order is reversed because PriorityQueue
+// pops the greatest item and the cheapest one is needed.
+struct Node {
+    priority: MInt, // cost plus A* heuristic, or just cost for Dijkstra
+    cost: MInt,
+    pos: MapPos,
+    source: uint,
+}
+
+impl Eq for Node {
+    fn eq(&self, other: &Node) -> MBool {
+        self.priority == other.priority && self.source == other.source
+    }
+}
+
+impl Ord for Node {
+    fn lt(&self, other: &Node) -> MBool {
+        self.priority > other.priority
+            || (self.priority == other.priority
+                && self.source > other.source)
+    }
+}
+
+// What the map was last filled by fill_map_within for
+#[deriving(Eq)]
+struct FillKey {
+    unit_id: UnitId,
+    pos: MapPos,
+    units_hash: u64,
+}
+
+// What the map was last filled by fill_flow_field for
+#[deriving(Eq)]
+struct FlowKey {
+    player_id: PlayerId,
+    targets: Vec<MapPos>,
+    units_hash: u64,
+}
+
+// Enemy tiles can be reached only as the last step of a path
+// (to know path to an enemy that is going to be attacked) and
+// friendly stacks can be passed through, but move can end
+// only on a tile where can_stop_at is true.
+//
+// All buffers are allocated once and reused by every query.
+// Custom MoveCost must depend only on units, or else cached
+// fill can be out of date.
 pub struct Pathfinder {
-    queue: Vec<MapPos>,
+    queue: PriorityQueue<Node>,
     map: Map,
+    tile_units: HashMap<(MInt, MInt), TileUnits>,
+    max_units_per_tile: uint, // from rules of the last searched state
+    move_cost: ~MoveCost,
+    target: Option<MapPos>, // Some during A* query
+    is_reverse: MBool, // true during flow field`s search
+    cost_limit: MInt,
+    zone_of_control_cost: Option<MInt>,
+    filled: Option<(FillKey, MInt)>, // key and cost limit of last fill
+    flow_filled: Option<FlowKey>,
 }
 
 fn create_tiles(tiles_count: MInt) -> Vec<Tile> {
     let mut tiles = Vec::new();
     for _ in range(0, tiles_count) {
         tiles.push(Tile {
-            cost: 0,
+            cost: max_cost(),
             parent: None,
+            can_stop: false,
+            source: 0,
+            generation: 0,
         });
     }
     tiles
@@ -53,39 +252,151 @@ fn create_tiles(tiles_count: MInt) -> Vec<Tile> {
 
 impl Pathfinder {
     pub fn new(map_size: Size2<MInt>) -> Pathfinder {
+        Pathfinder::with_move_cost(map_size, ~PlainMoveCost as ~MoveCost)
+    }
+
+    pub fn with_move_cost(
+        map_size: Size2<MInt>,
+        move_cost: ~MoveCost
+    ) -> Pathfinder {
         let tiles_count = map_size.w * map_size.h;
         Pathfinder {
-            queue: Vec::new(),
+            queue: PriorityQueue::new(),
             map: Map {
                 size: map_size,
                 tiles: create_tiles(tiles_count),
+                generation: 0,
+                visited: Vec::new(),
+            },
+            tile_units: HashMap::new(),
+            max_units_per_tile: 0,
+            move_cost: move_cost,
+            target: None,
+            is_reverse: false,
+            cost_limit: max_cost(),
+            zone_of_control_cost: None,
+            filled: None,
+            flow_filled: None,
+        }
+    }
+
+    // Entering a tile next to an enemy costs this much extra
+    pub fn set_zone_of_control_cost(&mut self, cost: Option<MInt>) {
+        self.zone_of_control_cost = cost;
+        self.filled = None;
+        self.flow_filled = None;
+    }
+
+    // Searches and split_path must price steps the same way
+    fn add_zone_of_control_cost(
+        &self,
+        step_cost: MInt,
+        is_near_enemy: MBool
+    ) -> MInt {
+        match self.zone_of_control_cost {
+            Some(cost) if is_near_enemy => step_cost + cost,
+            _ => step_cost,
+        }
+    }
+
+    fn get_priority(&self, cost: MInt, pos: MapPos) -> MInt {
+        match self.target {
+            Some(target) => {
+                cost + distance(pos, target) * self.move_cost.min_move_cost()
             },
+            None => cost,
+        }
+    }
+
+    fn collect_tile_units(&mut self, state: &GameState, unit: &Unit) {
+        self.tile_units.clear();
+        self.max_units_per_tile = state.rules.max_units_per_tile;
+        for (_, other) in state.units.iter() {
+            let is_enemy = other.player_id != unit.player_id;
+            {
+                let pos = (other.pos.x, other.pos.y);
+                let tile_units = self.tile_units.find_or_insert(
+                    pos, TileUnits::new());
+                tile_units.count += 1;
+                tile_units.has_enemy |= is_enemy;
+            }
+            if !is_enemy {
+                continue;
+            }
+            for i in range(0 as MInt, 6) {
+                let pos = Dir::get_neighbour_pos(other.pos, Dir::from_int(i));
+                let tile_units = self.tile_units.find_or_insert(
+                    (pos.x, pos.y), TileUnits::new());
+                tile_units.is_near_enemy = true;
+            }
+        }
+    }
+
+    // Returns (has_enemy, is_full, is_near_enemy)
+    fn get_tile_units(&self, pos: MapPos) -> (MBool, MBool, MBool) {
+        match self.tile_units.find(&(pos.x, pos.y)) {
+            Some(tile_units) => (
+                tile_units.has_enemy,
+                tile_units.count >= self.max_units_per_tile,
+                tile_units.is_near_enemy,
+            ),
+            None => (false, false, false),
         }
     }
 
     fn process_neighbour_pos(
         &mut self,
-        _: &GameState,
-        _: &Unit, // TODO: use unit`s type and action points later
+        state: &GameState,
+        unit: &Unit,
         original_pos: MapPos,
         neighbour_pos: MapPos
     ) {
-        let old_cost = self.map.tile(original_pos).cost;
-        let tile = self.map.tile_mut(neighbour_pos);
-        let new_cost: MInt = old_cost + 1;
-        if tile.cost > new_cost {
-            self.queue.push(neighbour_pos);
+        // Flow field is searched from targets, so steps go backwards
+        let (from, to) = if self.is_reverse {
+            (neighbour_pos, original_pos)
+        } else {
+            (original_pos, neighbour_pos)
+        };
+        let step_cost = match self.move_cost.move_cost(state, unit, from, to) {
+            Some(cost) => cost,
+            None => return,
+        };
+        assert!(step_cost > 0);
+        let (is_enemy, is_full, _) = self.get_tile_units(neighbour_pos);
+        // Unit can not start its move from enemy`s tile
+        if is_enemy && self.is_reverse {
+            return;
+        }
+        let (_, _, is_near_enemy) = self.get_tile_units(to);
+        let step_cost = self.add_zone_of_control_cost(
+            step_cost, is_near_enemy);
+        let (old_cost, source) = {
+            let tile = self.map.tile(original_pos);
+            (tile.cost, tile.source)
+        };
+        let new_cost = old_cost + step_cost;
+        if new_cost > self.cost_limit {
+            return;
+        }
+        let priority = self.get_priority(new_cost, neighbour_pos);
+        let tile = self.map.visit(neighbour_pos);
+        if tile.cost > new_cost
+            || (tile.cost == new_cost && tile.source > source)
+        {
+            if !is_enemy {
+                self.queue.push(Node {
+                    priority: priority,
+                    cost: new_cost,
+                    pos: neighbour_pos,
+                    source: source,
+                });
+            }
             // update neighbour tile info
             tile.cost = new_cost;
             let dir = Dir::get_dir_from_to(neighbour_pos, original_pos);
             tile.parent = Some(dir);
-        }
-    }
-
-    fn clean_map(&mut self) {
-        for tile in self.map.tiles.mut_iter() {
-            tile.cost = max_cost();
-            tile.parent = None;
+            tile.can_stop = !is_enemy && !is_full;
+            tile.source = source;
         }
     }
 
@@ -105,37 +416,287 @@ impl Pathfinder {
         }
     }
 
-    fn push_start_pos_to_queue(&mut self, start_pos: MapPos) {
-        self.queue.push(start_pos);
-        let start_tile = self.map.tile_mut(start_pos);
+    fn push_start_pos_to_queue(&mut self, start_pos: MapPos, source: uint) {
+        // Same flow field`s target can be listed twice
+        if !self.map.is_inboard(start_pos) || self.map.is_visited(start_pos) {
+            return;
+        }
+        let (is_enemy, is_full, _) = self.get_tile_units(start_pos);
+        let can_stop = !self.is_reverse || (!is_enemy && !is_full);
+        let priority = self.get_priority(0, start_pos);
+        self.queue.push(Node {
+            priority: priority,
+            cost: 0,
+            pos: start_pos,
+            source: source,
+        });
+        let start_tile = self.map.visit(start_pos);
         start_tile.cost = 0;
         start_tile.parent = None;
+        start_tile.can_stop = can_stop;
+        start_tile.source = source;
+    }
+
+    // Tile was pushed again with lower cost or source later
+    fn is_outdated(&self, node: &Node) -> MBool {
+        let tile = self.map.tile(node.pos);
+        node.cost > tile.cost
+            || (node.cost == tile.cost && node.source > tile.source)
+    }
+
+    // Processes queue until `target` is taken from it
+    // or until all tiles within `cost_limit` are processed
+    fn search(
+        &mut self,
+        state: &GameState,
+        unit: &Unit,
+        starts: &[MapPos],
+        target: Option<MapPos>,
+        cost_limit: MInt,
+        is_reverse: MBool
+    ) {
+        assert!(self.queue.is_empty());
+        self.target = target;
+        self.cost_limit = cost_limit;
+        self.is_reverse = is_reverse;
+        self.filled = None;
+        self.flow_filled = None;
+        self.map.start_new_generation();
+        self.collect_tile_units(state, unit);
+        for (source, start_pos) in starts.iter().enumerate() {
+            self.push_start_pos_to_queue(*start_pos, source);
+        }
+        loop {
+            let node = match self.queue.maybe_pop() {
+                Some(node) => node,
+                None => break,
+            };
+            if self.is_outdated(&node) {
+                continue;
+            }
+            if Some(node.pos) == target {
+                break;
+            }
+            self.try_to_push_neighbours(state, unit, node.pos);
+        }
+        self.queue.clear();
+        self.target = None;
+        self.is_reverse = false;
+    }
+
+    fn get_fill_key(&self, state: &GameState, unit: &Unit) -> FillKey {
+        FillKey {
+            unit_id: unit.id,
+            pos: unit.pos,
+            units_hash: units_hash(state),
+        }
+    }
+
+    // True if map is already filled for this unit and state
+    // at least up to `cost_limit`
+    fn is_filled(&self, key: &FillKey, cost_limit: MInt) -> MBool {
+        match self.filled {
+            Some((ref filled_key, limit)) => {
+                filled_key == key && limit >= cost_limit
+            },
+            None => false,
+        }
     }
 
+    // Dijkstra: tiles are taken from the queue in order of their cost
     pub fn fill_map(&mut self, state: &GameState, unit: &Unit) {
-        assert!(self.queue.len() == 0);
-        self.clean_map();
-        self.push_start_pos_to_queue(unit.pos);
-        while self.queue.len() != 0 {
-            let pos = self.queue.shift().unwrap();
-            self.try_to_push_neighbours(state, unit, pos);
+        self.fill_map_within(state, unit, max_cost());
+    }
+
+    // Fills only tiles that cost no more than `cost_limit`, other
+    // tiles are unreachable after it. Does nothing if map
+    // is already filled for the same unit and units.
+    pub fn fill_map_within(
+        &mut self,
+        state: &GameState,
+        unit: &Unit,
+        cost_limit: MInt
+    ) {
+        let key = self.get_fill_key(state, unit);
+        if self.is_filled(&key, cost_limit) {
+            return;
         }
+        self.search(state, unit, &[unit.pos], None, cost_limit, false);
+        self.filled = Some((key, cost_limit));
     }
 
-    pub fn get_path(&self, destination: MapPos) -> Vec<MapPos> {
+    fn get_path_to(&self, to: MapPos) -> Option<Path> {
+        if !self.can_stop_at(to) {
+            return None;
+        }
+        Some(Path {
+            positions: self.get_path(to),
+            cost: self.map.cost(to),
+        })
+    }
+
+    // A* query of one path. Map is filled only partially,
+    // so other tiles` costs and paths are not valid after it.
+    // Answered from the map if it is filled for this unit.
+    pub fn find_path(
+        &mut self,
+        state: &GameState,
+        unit: &Unit,
+        from: MapPos,
+        to: MapPos
+    ) -> Option<Path> {
+        if !self.map.is_inboard(from) || !self.map.is_inboard(to) {
+            return None;
+        }
+        if is_enemy_at(state, unit, to) || state.is_full_tile(to) {
+            return None;
+        }
+        if from == unit.pos {
+            let key = self.get_fill_key(state, unit);
+            if self.is_filled(&key, max_cost()) {
+                return self.get_path_to(to);
+            }
+        }
+        self.search(state, unit, &[from], Some(to), max_cost(), false);
+        self.get_path_to(to)
+    }
+
+    pub fn is_reachable(&self, pos: MapPos) -> MBool {
+        self.map.is_inboard(pos) && self.map.cost(pos) != max_cost()
+    }
+
+    pub fn can_stop_at(&self, pos: MapPos) -> MBool {
+        self.is_reachable(pos) && self.map.tile(pos).can_stop
+    }
+
+    // Total cost of the best path to `pos`, None if it is unreachable
+    pub fn get_cost(&self, pos: MapPos) -> Option<MInt> {
+        if self.is_reachable(pos) {
+            Some(self.map.cost(pos))
+        } else {
+            None
+        }
+    }
+
+    // Follows parents from `pos` to a start of the search
+    fn trace_parents(&self, pos: MapPos) -> Vec<MapPos> {
         let mut path = Vec::new();
-        let mut pos = destination;
-        assert!(self.map.is_inboard(pos));
-        path.push(destination);
+        if !self.is_reachable(pos) {
+            return path;
+        }
+        let mut pos = pos;
+        path.push(pos);
         while self.map.tile(pos).cost != 0 {
             let parent_dir = self.map.tile(pos).parent.unwrap();
             pos = Dir::get_neighbour_pos(pos, parent_dir);
             assert!(self.map.is_inboard(pos));
             path.push(pos);
         }
+        path
+    }
+
+    // Returns empty path if destination is unreachable
+    pub fn get_path(&self, destination: MapPos) -> Vec<MapPos> {
+        let mut path = self.trace_parents(destination);
         path.reverse();
         path
     }
+
+    // Flow field: one reverse Dijkstra from all `targets` gives every
+    // tile the cost of and the path to the closest of them, so it
+    // serves all units of `unit``s player that move like `unit`.
+    // Ties are resolved in favour of the target that goes first.
+    // Does nothing if map is already filled for the same player,
+    // targets and units. Only get_cost, is_reachable and
+    // get_flow_* queries are valid after it.
+    pub fn fill_flow_field(
+        &mut self,
+        state: &GameState,
+        unit: &Unit,
+        targets: &[MapPos]
+    ) {
+        let key = FlowKey {
+            player_id: unit.player_id,
+            targets: Vec::from_slice(targets),
+            units_hash: units_hash(state),
+        };
+        let is_filled = match self.flow_filled {
+            Some(ref filled_key) => *filled_key == key,
+            None => false,
+        };
+        if is_filled {
+            return;
+        }
+        self.search(state, unit, targets, None, max_cost(), true);
+        self.flow_filled = Some(key);
+    }
+
+    // Index of the closest target, None if no target is reachable
+    pub fn get_flow_target(&self, from: MapPos) -> Option<uint> {
+        if self.is_reachable(from) {
+            Some(self.map.tile(from).source)
+        } else {
+            None
+        }
+    }
+
+    // Path from `from` to the closest target, empty if there is none
+    pub fn get_flow_path(&self, from: MapPos) -> Vec<MapPos> {
+        self.trace_parents(from)
+    }
+
+    // Tiles where unit can end its move with `move_points`,
+    // in order of rows. Valid only after fill_map or fill_map_within.
+    pub fn get_reachable(&self, move_points: MInt) -> Vec<ReachableTile> {
+        let mut tiles = Vec::new();
+        for pos in self.map.visited.iter() {
+            let tile = self.map.tile(*pos);
+            if tile.can_stop && tile.cost <= move_points {
+                tiles.push(ReachableTile{pos: *pos, cost: tile.cost});
+            }
+        }
+        tiles.sort_by(|a, b| (a.pos.y, a.pos.x).cmp(&(b.pos.y, b.pos.x)));
+        tiles
+    }
+
+    // Splits path into parts that unit passes in one turn each,
+    // steps cost as much as in search, zone of control included.
+    // It is a forecast: tiles can become full before unit gets there.
+    pub fn split_path(
+        &self,
+        state: &GameState,
+        unit: &Unit,
+        path: &[MapPos],
+        move_points: MInt
+    ) -> Vec<Vec<MapPos>> {
+        let mut segments = Vec::new();
+        let mut start = 0;
+        while start + 1 < path.len() {
+            let mut cost = 0;
+            let mut end = start;
+            while end + 1 < path.len() {
+                let (from, to) = (path[end], path[end + 1]);
+                let step_cost = self.move_cost.move_cost(
+                    state, unit, from, to).map(|cost| {
+                        self.add_zone_of_control_cost(
+                            cost, is_near_enemy(state, unit, to))
+                    });
+                match step_cost {
+                    Some(step_cost) if cost + step_cost <= move_points => {
+                        cost += step_cost;
+                        end += 1;
+                    },
+                    _ => break,
+                }
+            }
+            if end == start {
+                break; // next step is too expensive or impossible
+            }
+            segments.push(Vec::from_slice(path.slice(start, end + 1)));
+            start = end;
+        }
+        segments
+    }
 }
 
 // vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
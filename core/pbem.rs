@@ -0,0 +1,368 @@
+// See LICENSE file for copyright and license details.
+
+use std::mem::replace;
+use std::io::{File, IoResult};
+use serialize::json;
+use serialize::json::Json;
+use collections::treemap::TreeMap;
+use types::{MBool, MInt, PlayerId, Size2};
+use core::{
+    Core,
+    CoreInterface,
+    Command,
+    CommandEndTurn,
+    Event,
+    PlayerKind,
+    PlayerAi,
+};
+use scenario::Scenario;
+use rules::Rules;
+use runner::Runner;
+use misc::try_read_file;
+use encoding::{
+    int_to_json,
+    get_object,
+    get_field,
+    get_list,
+    get_int,
+    get_str,
+    command_to_json,
+    json_to_command,
+    scenario_to_json,
+    json_to_scenario,
+};
+
+// Must be changed on every incompatible change of turn file format
+pub static TURN_FILE_VERSION: MInt = 2;
+
+// Turn file holds the whole game so far: scenario and commands of every
+// turn. Hash of every turn is chained with the hash of previous turn
+// (the first turn is chained with scenario`s hash), so editing
+// or dropping any turn breaks hashes of all following turns.
+
+#[deriving(Clone)]
+pub struct TurnRecord {
+    pub player_id: PlayerId,
+    pub commands: Vec<Command>,
+    pub hash: ~str,
+}
+
+#[deriving(Clone)]
+pub struct TurnFile {
+    pub scenario: Scenario,
+    pub turns: Vec<TurnRecord>,
+}
+
+// 64-bit FNV-1a: fixed and simple, so files stay valid across builds
+// and compiler versions. It only catches accidental corruption and
+// careless edits, anyone can recompute it after changing the file.
+fn hash_str(text: &str) -> ~str {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash *= 0x100000001b3;
+    }
+    format!("{:016x}", hash)
+}
+
+// Everything except the hash itself
+fn turn_to_json(
+    number: MInt,
+    player_id: PlayerId,
+    commands: &[Command]
+) -> TreeMap<~str, Json> {
+    let PlayerId(id) = player_id;
+    let mut obj = TreeMap::new();
+    obj.insert(~"turn", int_to_json(number));
+    obj.insert(~"player_id", int_to_json(id));
+    obj.insert(~"commands", json::List(commands.iter()
+        .map(|command| command_to_json(command)).collect()));
+    obj
+}
+
+fn get_turn_hash(prev_hash: &str, obj: &TreeMap<~str, Json>) -> ~str {
+    let text = json::Object(~obj.clone()).to_str();
+    hash_str(format!("{}{}", prev_hash, text).as_slice())
+}
+
+impl TurnFile {
+    pub fn new(scenario: Scenario) -> TurnFile {
+        TurnFile {
+            scenario: scenario,
+            turns: Vec::new(),
+        }
+    }
+
+    fn scenario_hash(&self) -> ~str {
+        hash_str(scenario_to_json(&self.scenario).to_str().as_slice())
+    }
+
+    fn last_hash(&self) -> ~str {
+        match self.turns.last() {
+            Some(turn) => turn.hash.clone(),
+            None => self.scenario_hash(),
+        }
+    }
+
+    pub fn add_turn(&mut self, player_id: PlayerId, commands: Vec<Command>) {
+        let number = self.turns.len() as MInt + 1;
+        let obj = turn_to_json(number, player_id, commands.as_slice());
+        let prev_hash = self.last_hash();
+        let hash = get_turn_hash(prev_hash.as_slice(), &obj);
+        self.turns.push(TurnRecord {
+            player_id: player_id,
+            commands: commands,
+            hash: hash,
+        });
+    }
+
+    pub fn to_str(&self) -> ~str {
+        let mut turns = Vec::new();
+        for (i, turn) in self.turns.iter().enumerate() {
+            let mut obj = turn_to_json(
+                i as MInt + 1, turn.player_id, turn.commands.as_slice());
+            obj.insert(~"hash", json::String(turn.hash.clone()));
+            turns.push(json::Object(~obj));
+        }
+        let mut obj = TreeMap::new();
+        obj.insert(~"version", int_to_json(TURN_FILE_VERSION));
+        obj.insert(~"scenario", scenario_to_json(&self.scenario));
+        obj.insert(~"turns", json::List(turns));
+        json::Object(~obj).to_pretty_str()
+    }
+
+    // Checks format, order of turns and hashes, but not commands
+    pub fn from_str(text: &str) -> Result<TurnFile, ~str> {
+        let json = match json::from_str(text) {
+            Ok(json) => json,
+            Err(_) => return Err(~"turn file is not valid JSON"),
+        };
+        let obj = try!(get_object(&json));
+        let version = try!(get_int(obj, "version"));
+        if version != TURN_FILE_VERSION {
+            return Err(format!("turn file version {} is not supported, \
+                expected {}", version, TURN_FILE_VERSION));
+        }
+        let scenario = try!(get_field(obj, "scenario"));
+        // Turn files are played with default rules, see to_core
+        let scenario = try!(json_to_scenario(scenario, &Rules::new()));
+        let mut file = TurnFile::new(scenario);
+        for (i, turn) in try!(get_list(obj, "turns")).iter().enumerate() {
+            let turn = try!(get_object(turn));
+            let number = i as MInt + 1;
+            if try!(get_int(turn, "turn")) != number {
+                return Err(format!("turn {} is out of order", number));
+            }
+            let player_id = PlayerId(try!(get_int(turn, "player_id")));
+            let mut commands = Vec::new();
+            for command in try!(get_list(turn, "commands")).iter() {
+                commands.push(try!(json_to_command(command)));
+            }
+            file.add_turn(player_id, commands);
+            let hash = try!(get_str(turn, "hash"));
+            if file.last_hash().as_slice() != hash {
+                return Err(format!("turn {}: hash mismatch, \
+                    file is corrupted or modified", number));
+            }
+        }
+        Ok(file)
+    }
+
+    pub fn load(path: &Path) -> Result<TurnFile, ~str> {
+        let text = match try_read_file(path) {
+            Ok(text) => text,
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        match TurnFile::from_str(text.as_slice()) {
+            Ok(file) => Ok(file),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> IoResult<()> {
+        let mut file = try!(File::create(path));
+        file.write_str(self.to_str().as_slice())
+    }
+
+    // Re-simulates every turn: players must move in order and
+    // every command must be legal. Returns player who moves next.
+    pub fn check_turns(&self) -> Result<PlayerId, ~str> {
+        for kind in self.scenario.players.iter() {
+            match *kind {
+                PlayerAi(_) => return Err(~"AI can not play by email"),
+                _ => {},
+            }
+        }
+        let mut runner = Runner::new(Core::from_scenario(&self.scenario));
+        for (i, turn) in self.turns.iter().enumerate() {
+            let number = i + 1;
+            if runner.winner().is_some() {
+                return Err(format!("turn {}: game is already over", number));
+            }
+            let player_id = runner.player_id();
+            if turn.player_id != player_id {
+                let PlayerId(id) = player_id;
+                return Err(format!("turn {}: it is player {}'s turn",
+                    number, id));
+            }
+            let count = turn.commands.len();
+            if count == 0 {
+                return Err(format!("turn {} is empty", number));
+            }
+            for (j, command) in turn.commands.iter().enumerate() {
+                let is_end_turn = match *command {
+                    CommandEndTurn => true,
+                    _ => false,
+                };
+                if is_end_turn != (j + 1 == count) {
+                    return Err(format!("turn {}: end_turn must be \
+                        the last command", number));
+                }
+                let result = runner.state(player_id).check_command(
+                    runner.map_size(), player_id, command);
+                match result {
+                    Ok(()) => {},
+                    Err(e) => return Err(format!("turn {}, command {}: {}",
+                        number, j + 1, e)),
+                }
+                runner.do_command(command.clone());
+            }
+        }
+        Ok(runner.player_id())
+    }
+
+    // `previous` is the file that importing player has sent: this one
+    // must repeat it and add exactly one turn of every other player
+    pub fn check_continues(&self, previous: &TurnFile) -> Result<(), ~str> {
+        if self.scenario_hash() != previous.scenario_hash() {
+            return Err(~"turn file belongs to other game");
+        }
+        let players_count = self.scenario.players.len();
+        let expected = previous.turns.len() + players_count - 1;
+        if self.turns.len() != expected {
+            return Err(format!("turn file is out of order: \
+                expected {} turns, found {}", expected, self.turns.len()));
+        }
+        for (i, turn) in previous.turns.iter().enumerate() {
+            if turn.hash != self.turns.get(i).hash {
+                return Err(format!("turn {} differs from previous file",
+                    i + 1));
+            }
+        }
+        Ok(())
+    }
+
+    // Core with every turn of the file already played
+    pub fn to_core(&self) -> ~Core {
+        let mut core = Core::from_scenario(&self.scenario);
+        for turn in self.turns.iter() {
+            for command in turn.commands.iter() {
+                core.do_command(command.clone());
+            }
+        }
+        core
+    }
+}
+
+// Plays one turn of imported game and exports it to a new turn file
+pub struct PbemCore {
+    core: ~Core,
+    file: TurnFile,
+    out_path: Path,
+    player_id: PlayerId,
+    commands: Vec<Command>,
+    is_turn_done: MBool,
+    error: Option<~str>,
+}
+
+impl PbemCore {
+    pub fn new(file: TurnFile, out_path: Path) -> Result<PbemCore, ~str> {
+        let player_id = try!(file.check_turns());
+        Ok(PbemCore {
+            core: file.to_core(),
+            file: file,
+            out_path: out_path,
+            player_id: player_id,
+            commands: Vec::new(),
+            is_turn_done: false,
+            error: None,
+        })
+    }
+
+    // Turn is saved before end_turn is applied: if file can not be
+    // written, the turn stays open and player can end it again
+    fn end_turn(&mut self) {
+        let mut commands = self.commands.clone();
+        commands.push(CommandEndTurn);
+        let mut file = self.file.clone();
+        file.add_turn(self.player_id, commands);
+        match file.save(&self.out_path) {
+            Ok(()) => {},
+            Err(e) => {
+                self.error = Some(format!("can not write {}: {}",
+                    self.out_path.display(), e));
+                return;
+            },
+        }
+        self.core.do_command(CommandEndTurn);
+        self.file = file;
+        self.commands.clear();
+        self.is_turn_done = true;
+    }
+}
+
+impl CoreInterface for PbemCore {
+    fn map_size(&self) -> Size2<MInt> {
+        self.core.map_size()
+    }
+
+    fn rules(&self) -> Rules {
+        self.core.rules()
+    }
+
+    fn players_count(&self) -> MInt {
+        self.core.players_count()
+    }
+
+    fn player_kind(&self, player_id: PlayerId) -> PlayerKind {
+        self.core.player_kind(player_id)
+    }
+
+    fn player_id(&self) -> PlayerId {
+        self.player_id
+    }
+
+    fn is_player_turn(&self) -> MBool {
+        !self.is_turn_done
+    }
+
+    // Importing player watches the whole game so far
+    fn get_event(&mut self) -> Option<Event> {
+        self.core.get_player_event(self.player_id)
+    }
+
+    fn take_error(&mut self) -> Option<~str> {
+        replace(&mut self.error, None)
+    }
+
+    // Rejected commands are not recorded, otherwise the turn file
+    // would fail check_turns on import
+    fn do_command(&mut self, command: Command) {
+        if self.is_turn_done {
+            return;
+        }
+        match command {
+            CommandEndTurn => {
+                self.end_turn();
+                return;
+            },
+            _ => {},
+        }
+        match self.core.try_command(command.clone()) {
+            Ok(()) => self.commands.push(command),
+            Err(e) => self.error = Some(e),
+        }
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
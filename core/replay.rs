@@ -0,0 +1,167 @@
+// See LICENSE file for copyright and license details.
+
+use std::io::{File, IoResult};
+use collections::hashmap::HashMap;
+use types::{MInt, UnitId, PlayerId, MapPos};
+use core::{
+    Event,
+    EventMove,
+    EventEndTurn,
+    EventCreateUnit,
+    EventAttackUnit,
+    EventMoveOrder,
+};
+
+fn pos_to_str(pos: MapPos) -> ~str {
+    format!("{},{}", pos.x, pos.y)
+}
+
+fn path_to_str(path: &Vec<MapPos>) -> ~str {
+    let path: Vec<~str> = path.iter().map(|pos| pos_to_str(*pos)).collect();
+    path.as_slice().connect(" ")
+}
+
+pub fn event_to_str(event: &Event) -> ~str {
+    match *event {
+        EventMove(UnitId(id), ref path) => {
+            format!("move {} {}", id, path_to_str(path))
+        },
+        EventEndTurn(PlayerId(old_id), PlayerId(new_id)) => {
+            format!("end_turn {} {}", old_id, new_id)
+        },
+        EventCreateUnit(UnitId(id), pos, PlayerId(player_id)) => {
+            format!("create_unit {} {} {}", id, pos_to_str(pos), player_id)
+        },
+        EventAttackUnit(UnitId(attacker_id), UnitId(defender_id)) => {
+            format!("attack {} {}", attacker_id, defender_id)
+        },
+        EventMoveOrder(UnitId(id), ref path) if path.len() == 0 => {
+            format!("move_order {} done", id)
+        },
+        EventMoveOrder(UnitId(id), ref path) => {
+            format!("move_order {} {}", id, path_to_str(path))
+        },
+    }
+}
+
+// Describes events for people reading logs:
+// "Turn 3: Player 1's Unit#5 moved (2,3)→(4,3)".
+// Turns are counted from 1 and every round of all players is
+// one turn. Owners of units are remembered from creation events,
+// so events must be formatted in order starting from the first one.
+pub struct EventFormatter {
+    turn: MInt,
+    owners: HashMap<UnitId, PlayerId>,
+}
+
+fn unit_to_str(id: UnitId) -> ~str {
+    let UnitId(id) = id;
+    format!("Unit\#{}", id)
+}
+
+fn pretty_pos_to_str(pos: MapPos) -> ~str {
+    format!("({},{})", pos.x, pos.y)
+}
+
+impl EventFormatter {
+    pub fn new() -> EventFormatter {
+        EventFormatter {
+            turn: 1,
+            owners: HashMap::new(),
+        }
+    }
+
+    pub fn turn(&self) -> MInt {
+        self.turn
+    }
+
+    pub fn owner(&self, unit_id: UnitId) -> Option<PlayerId> {
+        self.owners.find(&unit_id).map(|id| *id)
+    }
+
+    // Player who made the event
+    pub fn player_of(&self, event: &Event) -> Option<PlayerId> {
+        match *event {
+            EventMove(id, _) | EventMoveOrder(id, _) => self.owner(id),
+            EventAttackUnit(attacker_id, _) => self.owner(attacker_id),
+            EventEndTurn(old_id, _) => Some(old_id),
+            EventCreateUnit(_, _, player_id) => Some(player_id),
+        }
+    }
+
+    fn owned_unit_to_str(&self, id: UnitId) -> ~str {
+        match self.owner(id) {
+            Some(PlayerId(player_id)) => {
+                format!("Player {}'s {}", player_id, unit_to_str(id))
+            },
+            None => unit_to_str(id),
+        }
+    }
+
+    fn describe(&self, event: &Event) -> ~str {
+        match *event {
+            EventMove(id, ref path) => {
+                format!("{} moved {}→{}",
+                    self.owned_unit_to_str(id),
+                    pretty_pos_to_str(*path.get(0)),
+                    pretty_pos_to_str(*path.last().unwrap()))
+            },
+            EventEndTurn(PlayerId(old_id), PlayerId(new_id)) => {
+                format!("Player {} ended turn, Player {} moves",
+                    old_id, new_id)
+            },
+            EventCreateUnit(id, pos, _) => {
+                format!("{} created at {}",
+                    self.owned_unit_to_str(id), pretty_pos_to_str(pos))
+            },
+            EventAttackUnit(attacker_id, defender_id) => {
+                format!("{} destroyed {}",
+                    self.owned_unit_to_str(attacker_id),
+                    self.owned_unit_to_str(defender_id))
+            },
+            EventMoveOrder(id, ref path) if path.len() == 0 => {
+                format!("{} has no move order now",
+                    self.owned_unit_to_str(id))
+            },
+            EventMoveOrder(id, ref path) => {
+                format!("{} ordered to move to {}",
+                    self.owned_unit_to_str(id),
+                    pretty_pos_to_str(*path.last().unwrap()))
+            },
+        }
+    }
+
+    pub fn format(&mut self, event: &Event) -> ~str {
+        match *event {
+            EventCreateUnit(id, _, player_id) => {
+                self.owners.insert(id, player_id);
+            },
+            _ => {},
+        }
+        let text = format!("Turn {}: {}", self.turn, self.describe(event));
+        match *event {
+            EventEndTurn(_, PlayerId(0)) => self.turn += 1,
+            _ => {},
+        }
+        text
+    }
+}
+
+// Writes `header` lines as comments followed by one event per line
+pub fn write_replay(
+    path: &Path,
+    header: &[~str],
+    events: &[Event]
+) -> IoResult<()> {
+    let mut file = try!(File::create(path));
+    for line in header.iter() {
+        try!(file.write_str("# "));
+        try!(file.write_line(line.as_slice()));
+    }
+    for event in events.iter() {
+        try!(file.write_line(event_to_str(event).as_slice()));
+    }
+    Ok(())
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -0,0 +1,31 @@
+// See LICENSE file for copyright and license details.
+
+use types::MInt;
+
+// Numbers that Core, players` game states, pathfinder and AIs
+// must agree on. Core owns them, everybody else gets a copy
+// with CoreInterface::rules.
+#[deriving(Clone, Eq)]
+pub struct Rules {
+    pub move_distance: MInt, // steps per turn, every step costs 1
+    pub attack_distance: MInt,
+    pub max_units_per_tile: uint,
+}
+
+impl Rules {
+    // TODO: Get distances from UnitType
+    pub fn new() -> Rules {
+        Rules {
+            move_distance: 3,
+            attack_distance: 1,
+            max_units_per_tile: 6,
+        }
+    }
+
+    // Enemies farther than that can not attack this turn
+    pub fn sight(&self) -> MInt {
+        self.move_distance + self.attack_distance
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
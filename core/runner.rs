@@ -0,0 +1,209 @@
+// See LICENSE file for copyright and license details.
+
+use collections::hashmap::HashMap;
+use types::{MBool, MInt, PlayerId, SubscriberId, Size2};
+use core::{
+    Core,
+    CoreInterface,
+    Command,
+    CommandEndTurn,
+    Event,
+    EventAttackUnit,
+};
+use game_state::GameState;
+use rules::Rules;
+use checker::Divergence;
+use observer::Delivery;
+use ai::{Ai, get_ais};
+
+// Drives Core without any frontend: keeps players` game states
+// up to date and asks AIs for commands when it is their turn.
+pub struct Runner {
+    core: ~Core,
+    states: HashMap<PlayerId, GameState>,
+    ais: HashMap<PlayerId, ~Ai>,
+    ai_events: HashMap<PlayerId, Vec<Event>>,
+    ai_commands: Vec<Command>,
+    histories: HashMap<PlayerId, Vec<Event>>,
+    units_lost: Vec<MInt>,
+    turns: MInt,
+}
+
+fn get_game_states(
+    players_count: MInt,
+    rules: &Rules
+) -> HashMap<PlayerId, GameState> {
+    let mut m = HashMap::new();
+    for i in range(0, players_count) {
+        m.insert(PlayerId(i), GameState::new(rules.clone()));
+    }
+    m
+}
+
+fn get_event_lists(players_count: MInt) -> HashMap<PlayerId, Vec<Event>> {
+    let mut m = HashMap::new();
+    for i in range(0, players_count) {
+        m.insert(PlayerId(i), Vec::new());
+    }
+    m
+}
+
+impl Runner {
+    pub fn new(core: ~Core) -> Runner {
+        let players_count = core.players_count();
+        let ais = get_ais(&*core as &CoreInterface);
+        let states = get_game_states(players_count, core.state().rules());
+        let mut runner = Runner {
+            core: core,
+            states: states,
+            ais: ais,
+            ai_events: get_event_lists(players_count),
+            ai_commands: Vec::new(),
+            histories: get_event_lists(players_count),
+            units_lost: Vec::from_elem(players_count as uint, 0),
+            turns: 0,
+        };
+        runner.pump_events();
+        runner
+    }
+
+    pub fn player_id(&self) -> PlayerId {
+        self.core.player_id()
+    }
+
+    pub fn map_size(&self) -> Size2<MInt> {
+        self.core.map_size()
+    }
+
+    pub fn players_count(&self) -> MInt {
+        self.core.players_count()
+    }
+
+    pub fn rules<'a>(&'a self) -> &'a Rules {
+        self.core.state().rules()
+    }
+
+    pub fn state<'a>(&'a self, player_id: PlayerId) -> &'a GameState {
+        self.states.get(&player_id)
+    }
+
+    // All events that player has received since the start of the game
+    pub fn history<'a>(&'a self, player_id: PlayerId) -> &'a [Event] {
+        self.histories.get(&player_id).as_slice()
+    }
+
+    // All events from the first player`s point of view
+    pub fn log<'a>(&'a self) -> &'a [Event] {
+        self.history(PlayerId(0))
+    }
+
+    pub fn turns(&self) -> MInt {
+        self.turns
+    }
+
+    pub fn units_lost(&self, player_id: PlayerId) -> MInt {
+        let PlayerId(id) = player_id;
+        *self.units_lost.get(id as uint)
+    }
+
+    pub fn winner(&self) -> Option<PlayerId> {
+        self.core.winner()
+    }
+
+    pub fn subscribe(
+        &mut self,
+        view: Option<PlayerId>,
+        delivery: Delivery
+    ) -> SubscriberId {
+        self.core.subscribe(view, delivery)
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriberId) {
+        self.core.unsubscribe(id);
+    }
+
+    pub fn get_subscriber_event(&mut self, id: SubscriberId) -> Option<Event> {
+        self.core.get_subscriber_event(id)
+    }
+
+    pub fn enable_consistency_check(&mut self) {
+        self.core.enable_consistency_check();
+    }
+
+    pub fn divergences<'a>(&'a self) -> &'a [Divergence] {
+        self.core.divergences()
+    }
+
+    pub fn is_ai(&self, player_id: PlayerId) -> MBool {
+        self.ais.contains_key(&player_id)
+    }
+
+    pub fn is_ai_turn(&self) -> MBool {
+        self.is_ai(self.core.player_id())
+    }
+
+    pub fn get_ai_command(&mut self) -> Command {
+        let player_id = self.core.player_id();
+        if self.ai_commands.len() == 0 {
+            let state = self.states.get(&player_id);
+            let events = self.ai_events.get_mut(&player_id);
+            let ai = self.ais.get_mut(&player_id);
+            self.ai_commands = ai.take_turn(state, events.as_slice());
+            events.clear();
+        }
+        self.ai_commands.shift().unwrap()
+    }
+
+    pub fn do_command(&mut self, command: Command) {
+        match command {
+            CommandEndTurn => self.turns += 1,
+            _ => {},
+        }
+        self.core.do_command(command);
+        self.pump_events();
+    }
+
+    // Plays AI turns until human`s turn, victory or turns limit
+    pub fn play_ai_turns(&mut self, max_turns: MInt) {
+        while self.is_ai_turn()
+            && self.winner().is_none()
+            && self.turns < max_turns
+        {
+            let command = self.get_ai_command();
+            self.do_command(command);
+        }
+    }
+
+    fn count_losses(&mut self, event: &Event) {
+        match *event {
+            EventAttackUnit(_, defender_id) => {
+                let state = self.states.get(&PlayerId(0));
+                let PlayerId(id) = state.units.get(&defender_id).player_id;
+                *self.units_lost.get_mut(id as uint) += 1;
+            },
+            _ => {},
+        }
+    }
+
+    fn pump_events(&mut self) {
+        for i in range(0, self.core.players_count()) {
+            let player_id = PlayerId(i);
+            loop {
+                let event = match self.core.get_player_event(player_id) {
+                    Some(event) => event,
+                    None => break,
+                };
+                if i == 0 {
+                    self.count_losses(&event);
+                }
+                self.histories.get_mut(&player_id).push(event.clone());
+                self.states.get_mut(&player_id).apply_event(&event);
+                if self.ais.contains_key(&player_id) {
+                    self.ai_events.get_mut(&player_id).push(event);
+                }
+            }
+        }
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
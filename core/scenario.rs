@@ -0,0 +1,185 @@
+// See LICENSE file for copyright and license details.
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+use cgmath::vector::Vec2;
+use types::{Size2, MInt};
+use core::{PlayerKind, PlayerAi, PlayerHuman};
+use conf::{Config, ConfigError, ConfigErrorBadField};
+use rules::Rules;
+use ai::AI_NAMES;
+
+#[deriving(Decodable, Clone)]
+pub struct UnitInfo {
+    pub player_id: MInt,
+    pub x: MInt,
+    pub y: MInt,
+}
+
+#[deriving(Clone)]
+pub struct Scenario {
+    pub map_size: Size2<MInt>,
+    pub players: Vec<PlayerKind>,
+    pub units: Vec<UnitInfo>,
+}
+
+impl Scenario {
+    // Only `players` is optional
+    fn new_config() -> Config {
+        let mut config = Config::new();
+        config.set_default("players", "[\"human\", \"greedy\"]");
+        config
+    }
+
+    fn from_fields(
+        config: &Config,
+        rules: &Rules
+    ) -> Result<Scenario, ConfigError> {
+        let names: Vec<~str> = try!(config.get("players"));
+        let scenario = Scenario {
+            map_size: try!(config.get("map_size")),
+            players: names.iter()
+                .map(|name| PlayerKind::from_str(name.as_slice()))
+                .collect(),
+            units: try!(config.get("units")),
+        };
+        let bad_field = |name: &str, reason: ~str| {
+            let source = config.source_of(name);
+            ConfigErrorBadField(source, name.to_owned(), reason)
+        };
+        match scenario.check_map_size() {
+            Ok(()) => {},
+            Err(e) => return Err(bad_field("map_size", e)),
+        }
+        match scenario.check_players() {
+            Ok(()) => {},
+            Err(e) => return Err(bad_field("players", e)),
+        }
+        match scenario.check_units(rules) {
+            Ok(()) => {},
+            Err(e) => return Err(bad_field("units", e)),
+        }
+        Ok(scenario)
+    }
+
+    pub fn check_map_size(&self) -> Result<(), ~str> {
+        if self.map_size.w <= 0 || self.map_size.h <= 0 {
+            Err(~"map is empty")
+        } else {
+            Ok(())
+        }
+    }
+
+    // Every AI must be known, see ai::new_ai
+    pub fn check_players(&self) -> Result<(), ~str> {
+        if self.players.len() < 2 {
+            return Err(~"game needs at least two players");
+        }
+        for kind in self.players.iter() {
+            match *kind {
+                PlayerAi(ref name) => {
+                    if !AI_NAMES.iter().any(|n| *n == name.as_slice()) {
+                        return Err(format!("unknown AI: '{}', expected \
+                            human or one of: {}", *name,
+                            AI_NAMES.connect(", ")));
+                    }
+                },
+                PlayerHuman => {},
+            }
+        }
+        Ok(())
+    }
+
+    // Core expects every unit inside of the map, owned by
+    // existing player and not crowded on one tile
+    pub fn check_units(&self, rules: &Rules) -> Result<(), ~str> {
+        let players_count = self.players.len() as MInt;
+        for unit in self.units.iter() {
+            if unit.player_id < 0 || unit.player_id >= players_count {
+                return Err(format!("unit of unknown player {}",
+                    unit.player_id));
+            }
+            if unit.x < 0 || unit.y < 0
+                || unit.x >= self.map_size.w || unit.y >= self.map_size.h
+            {
+                return Err(format!("unit at {},{} is out of the map",
+                    unit.x, unit.y));
+            }
+            let count = self.units.iter()
+                .filter(|u| u.x == unit.x && u.y == unit.y)
+                .count();
+            if count > rules.max_units_per_tile {
+                return Err(format!("too many units at {},{}",
+                    unit.x, unit.y));
+            }
+        }
+        Ok(())
+    }
+
+    // All checks for scenarios that do not come from config
+    pub fn check(&self, rules: &Rules) -> Result<(), ~str> {
+        try!(self.check_map_size());
+        try!(self.check_players());
+        self.check_units(rules)
+    }
+
+    pub fn from_config(
+        path: &str,
+        rules: &Rules
+    ) -> Result<Scenario, ConfigError> {
+        let mut config = Scenario::new_config();
+        try!(config.add_file(&Path::new(path)));
+        Scenario::from_fields(&config, rules)
+    }
+
+    // Bundled scenario with user`s copy and command line on top
+    pub fn from_layered_config(
+        name: &str,
+        overrides: &[~str],
+        rules: &Rules
+    ) -> Result<Scenario, ConfigError> {
+        let mut config = Scenario::new_config();
+        try!(config.add_layers(name, overrides));
+        Scenario::from_fields(&config, rules)
+    }
+
+    // Every player gets his own vertical band of the map
+    // and his units are placed randomly inside of it.
+    pub fn generate(
+        map_size: Size2<MInt>,
+        players: Vec<PlayerKind>,
+        units_per_player: MInt,
+        seed: u32
+    ) -> Scenario {
+        let mut rng: XorShiftRng = SeedableRng::from_seed(
+            [0x193a6754 ^ seed, 0xa8a7d469, 0x97830e05, 0x113ba7bb]);
+        let players_count = players.len() as MInt;
+        let band_w = map_size.w / players_count;
+        assert!(band_w * map_size.h >= units_per_player);
+        let mut units = Vec::new();
+        for player_id in range(0, players_count) {
+            let mut positions = Vec::new();
+            while positions.len() < units_per_player as uint {
+                let pos = Vec2 {
+                    x: player_id * band_w + rng.gen_range(0, band_w),
+                    y: rng.gen_range(0, map_size.h),
+                };
+                if positions.contains(&pos) {
+                    continue;
+                }
+                positions.push(pos);
+                units.push(UnitInfo {
+                    player_id: player_id,
+                    x: pos.x,
+                    y: pos.y,
+                });
+            }
+        }
+        Scenario {
+            map_size: map_size,
+            players: players,
+            units: units,
+        }
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -0,0 +1,351 @@
+// See LICENSE file for copyright and license details.
+
+use std::io::{IoResult, Listener, Acceptor, BufferedReader};
+use std::io::net::ip::{SocketAddr, Ipv4Addr};
+use std::io::net::tcp::{TcpListener, TcpAcceptor, TcpStream};
+use collections::hashmap::HashMap;
+use types::{MBool, MInt, PlayerId};
+use core::{Core, Command};
+use runner::Runner;
+use net::{
+    PROTOCOL_VERSION,
+    ClientMessage,
+    ClientHello,
+    ClientCommand,
+    ClientBye,
+    ServerMessage,
+    Welcome,
+    ServerWelcome,
+    ServerEvent,
+    ServerError,
+    ServerGameOver,
+    str_to_client_message,
+    server_message_to_str,
+};
+
+type ConnectionId = uint;
+
+// Changes that server`s host may want to show, see Server::set_status
+pub enum ServerStatus {
+    StatusPlayerConnected(PlayerId),
+    StatusPlayerDisconnected(PlayerId),
+}
+
+enum ConnectionEvent {
+    Connected(ConnectionId, TcpStream),
+    Received(ConnectionId, Result<ClientMessage, ~str>),
+    Disconnected(ConnectionId),
+}
+
+struct Connection {
+    stream: TcpStream,
+    player_id: Option<PlayerId>,
+    sent_events_count: uint,
+}
+
+// One task per connection, stops on "bye" or when client goes away
+fn receive_messages(
+    id: ConnectionId,
+    stream: TcpStream,
+    sender: Sender<ConnectionEvent>
+) {
+    let mut reader = BufferedReader::new(stream);
+    loop {
+        let line = match reader.read_line() {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        match str_to_client_message(line.as_slice()) {
+            Ok(ClientBye) => break,
+            message => { let _ = sender.send_opt(Received(id, message)); },
+        }
+    }
+    let _ = sender.send_opt(Disconnected(id));
+}
+
+fn accept_connections(
+    mut acceptor: TcpAcceptor,
+    sender: Sender<ConnectionEvent>,
+    stop_receiver: Receiver<()>
+) {
+    let mut next_id = 0;
+    for stream in acceptor.incoming() {
+        if stop_receiver.try_recv().is_ok() {
+            break;
+        }
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let id = next_id;
+        next_id += 1;
+        let reader_stream = stream.clone();
+        let reader_sender = sender.clone();
+        let _ = sender.send_opt(Connected(id, stream));
+        spawn(proc() receive_messages(id, reader_stream, reader_sender));
+    }
+}
+
+// Address that accepting task can be woken up with
+fn get_local_addr(addr: SocketAddr) -> SocketAddr {
+    match addr.ip {
+        Ipv4Addr(0, 0, 0, 0) => SocketAddr {
+            ip: Ipv4Addr(127, 0, 0, 1),
+            port: addr.port,
+        },
+        _ => addr,
+    }
+}
+
+// Hosts one game: human players join over TCP, AI players
+// are played by server itself.
+pub struct Server {
+    runner: Runner,
+    acceptor: Option<TcpAcceptor>,
+    addr: SocketAddr,
+    connections: HashMap<ConnectionId, Connection>,
+    max_turns: MInt,
+    status: Option<Sender<ServerStatus>>,
+}
+
+impl Server {
+    // Port 0 means any free port, see `addr`
+    pub fn new(
+        core: ~Core,
+        addr: SocketAddr,
+        max_turns: MInt
+    ) -> IoResult<Server> {
+        let mut listener = try!(TcpListener::bind(addr));
+        let addr = try!(listener.socket_name());
+        let acceptor = try!(listener.listen());
+        Ok(Server {
+            runner: Runner::new(core),
+            acceptor: Some(acceptor),
+            addr: addr,
+            connections: HashMap::new(),
+            max_turns: max_turns,
+            status: None,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // Server keeps running if receiver is gone
+    pub fn set_status(&mut self, sender: Sender<ServerStatus>) {
+        self.status = Some(sender);
+    }
+
+    fn report(&self, status: ServerStatus) {
+        match self.status {
+            Some(ref sender) => {
+                let _ = sender.send_opt(status);
+            },
+            None => {},
+        }
+    }
+
+    fn is_game_over(&self) -> MBool {
+        self.runner.winner().is_some()
+            || self.runner.turns() >= self.max_turns
+    }
+
+    // Plays the game till the end, returns the winner
+    pub fn run(&mut self) -> Option<PlayerId> {
+        let (sender, receiver) = channel();
+        let (stop_sender, stop_receiver) = channel();
+        let acceptor = self.acceptor.take_unwrap();
+        spawn(proc() accept_connections(acceptor, sender, stop_receiver));
+        loop {
+            self.runner.play_ai_turns(self.max_turns);
+            self.send_events();
+            if self.is_game_over() {
+                break;
+            }
+            match receiver.recv() {
+                Connected(id, stream) => {
+                    self.connections.insert(id, Connection {
+                        stream: stream,
+                        player_id: None,
+                        sent_events_count: 0,
+                    });
+                },
+                Received(id, Ok(ClientHello(version, player_id))) => {
+                    self.handle_hello(id, version, player_id);
+                },
+                Received(id, Ok(ClientCommand(command))) => {
+                    self.handle_command(id, command);
+                },
+                Received(_, Ok(ClientBye)) => {},
+                Received(id, Err(e)) => self.send(id, &ServerError(e)),
+                Disconnected(id) => self.disconnect(id),
+            }
+        }
+        let winner = self.runner.winner();
+        let ids: Vec<ConnectionId> = self.connections.keys()
+            .map(|id| *id).collect();
+        for id in ids.iter() {
+            self.send(*id, &ServerGameOver(winner));
+        }
+        self.connections.clear();
+        stop_sender.send(());
+        let _ = TcpStream::connect(get_local_addr(self.addr));
+        winner
+    }
+
+    fn send(&mut self, id: ConnectionId, message: &ServerMessage) {
+        let is_ok = match self.connections.find_mut(&id) {
+            Some(connection) => {
+                let line = server_message_to_str(message);
+                connection.stream.write_line(line.as_slice()).is_ok()
+            },
+            None => true,
+        };
+        if !is_ok {
+            self.disconnect(id);
+        }
+    }
+
+    fn disconnect(&mut self, id: ConnectionId) {
+        match self.connections.pop(&id) {
+            Some(Connection{player_id: Some(player_id), ..}) => {
+                self.report(StatusPlayerDisconnected(player_id));
+            },
+            _ => {},
+        }
+    }
+
+    fn is_free_slot(&self, player_id: PlayerId) -> MBool {
+        let PlayerId(n) = player_id;
+        n >= 0 && n < self.runner.players_count()
+            && !self.runner.is_ai(player_id)
+            && !self.connections.values()
+                .any(|c| c.player_id == Some(player_id))
+    }
+
+    fn find_free_slot(&self) -> Option<PlayerId> {
+        for n in range(0, self.runner.players_count()) {
+            if self.is_free_slot(PlayerId(n)) {
+                return Some(PlayerId(n));
+            }
+        }
+        None
+    }
+
+    fn handle_hello(
+        &mut self,
+        id: ConnectionId,
+        version: MInt,
+        requested_player_id: Option<PlayerId>
+    ) {
+        let is_joined = match self.connections.find(&id) {
+            Some(connection) => connection.player_id.is_some(),
+            None => return,
+        };
+        if is_joined {
+            self.send(id, &ServerError(~"already joined"));
+            return;
+        }
+        if version != PROTOCOL_VERSION {
+            let message = format!("protocol version mismatch: \
+                server uses {}, client uses {}", PROTOCOL_VERSION, version);
+            self.send(id, &ServerError(message));
+            return;
+        }
+        let player_id = match requested_player_id {
+            Some(player_id) if self.is_free_slot(player_id) => player_id,
+            Some(PlayerId(n)) => {
+                let message = format!("player {} is not available", n);
+                self.send(id, &ServerError(message));
+                return;
+            },
+            None => match self.find_free_slot() {
+                Some(player_id) => player_id,
+                None => {
+                    self.send(id, &ServerError(~"no free player slots"));
+                    return;
+                },
+            },
+        };
+        let events_count = self.runner.history(player_id).len();
+        self.send(id, &ServerWelcome(Welcome {
+            player_id: player_id,
+            players_count: self.runner.players_count(),
+            map_size: self.runner.map_size(),
+            rules: self.runner.rules().clone(),
+            events_count: events_count as MInt,
+        }));
+        let events: Vec<ServerMessage> = self.runner.history(player_id)
+            .iter().map(|event| ServerEvent(event.clone())).collect();
+        for message in events.iter() {
+            self.send(id, message);
+        }
+        let is_connected = match self.connections.find_mut(&id) {
+            Some(connection) => {
+                connection.player_id = Some(player_id);
+                connection.sent_events_count = events_count;
+                true
+            },
+            None => false,
+        };
+        if is_connected {
+            self.report(StatusPlayerConnected(player_id));
+        }
+    }
+
+    fn handle_command(&mut self, id: ConnectionId, command: Command) {
+        let player_id = match self.connections.find(&id) {
+            Some(connection) => connection.player_id,
+            None => return,
+        };
+        let player_id = match player_id {
+            Some(player_id) => player_id,
+            None => {
+                self.send(id, &ServerError(~"hello expected"));
+                return;
+            },
+        };
+        if player_id != self.runner.player_id() {
+            self.send(id, &ServerError(~"not your turn"));
+            return;
+        }
+        let result = self.runner.state(player_id)
+            .check_command(self.runner.map_size(), player_id, &command);
+        match result {
+            Ok(()) => self.runner.do_command(command),
+            Err(e) => self.send(id, &ServerError(e)),
+        }
+    }
+
+    // Sends every joined client events that were not sent to it yet
+    fn send_events(&mut self) {
+        let ids: Vec<ConnectionId> = self.connections.keys()
+            .map(|id| *id).collect();
+        for id in ids.iter() {
+            let (player_id, sent_count) = match self.connections.find(id) {
+                Some(connection) => match connection.player_id {
+                    Some(player_id) => {
+                        (player_id, connection.sent_events_count)
+                    },
+                    None => continue,
+                },
+                None => continue,
+            };
+            let events: Vec<ServerMessage> = self.runner.history(player_id)
+                .slice_from(sent_count).iter()
+                .map(|event| ServerEvent(event.clone())).collect();
+            for message in events.iter() {
+                self.send(*id, message);
+            }
+            match self.connections.find_mut(id) {
+                Some(connection) => {
+                    connection.sent_events_count += events.len();
+                },
+                None => {},
+            }
+        }
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -0,0 +1,123 @@
+// See LICENSE file for copyright and license details.
+
+use types::{MBool, MInt, MapPos, Size2};
+use core::{Core, PlayerKind};
+use scenario::{Scenario, UnitInfo};
+use rules::Rules;
+
+// Starts Core without reading any files, so tools and tests can
+// run many independent games in one process:
+//
+//     let core = try!(GameSettings::new(Size2{w: 9, h: 6})
+//         .player(PlayerHuman)
+//         .player(PlayerAi(~"greedy"))
+//         .random_units(3, seed)
+//         .build());
+#[deriving(Clone)]
+pub struct GameSettings {
+    map_size: Size2<MInt>,
+    players: Vec<PlayerKind>,
+    units: Vec<UnitInfo>,
+    random_units: Option<(MInt, u32)>, // units per player, seed
+    rules: Rules,
+    is_consistency_check: MBool,
+}
+
+impl GameSettings {
+    pub fn new(map_size: Size2<MInt>) -> GameSettings {
+        GameSettings {
+            map_size: map_size,
+            players: Vec::new(),
+            units: Vec::new(),
+            random_units: None,
+            rules: Rules::new(),
+            is_consistency_check: false,
+        }
+    }
+
+    pub fn from_scenario(scenario: &Scenario) -> GameSettings {
+        GameSettings {
+            map_size: scenario.map_size,
+            players: scenario.players.clone(),
+            units: scenario.units.clone(),
+            random_units: None,
+            rules: Rules::new(),
+            is_consistency_check: false,
+        }
+    }
+
+    pub fn player(mut self, kind: PlayerKind) -> GameSettings {
+        self.players.push(kind);
+        self
+    }
+
+    pub fn players(mut self, kinds: Vec<PlayerKind>) -> GameSettings {
+        self.players = kinds;
+        self
+    }
+
+    pub fn unit(mut self, player_id: MInt, pos: MapPos) -> GameSettings {
+        self.units.push(UnitInfo {
+            player_id: player_id,
+            x: pos.x,
+            y: pos.y,
+        });
+        self
+    }
+
+    // Placed as in Scenario::generate
+    pub fn random_units(mut self, count: MInt, seed: u32) -> GameSettings {
+        self.random_units = Some((count, seed));
+        self
+    }
+
+    pub fn rules(mut self, rules: Rules) -> GameSettings {
+        self.rules = rules;
+        self
+    }
+
+    // See Core::enable_consistency_check
+    pub fn consistency_check(mut self) -> GameSettings {
+        self.is_consistency_check = true;
+        self
+    }
+
+    pub fn to_scenario(&self) -> Result<Scenario, ~str> {
+        let players_count = self.players.len() as MInt;
+        if players_count == 0 {
+            return Err(~"game needs at least two players");
+        }
+        let scenario = match self.random_units {
+            Some(_) if self.units.len() != 0 => {
+                return Err(~"fixed and random units can not be mixed");
+            },
+            Some((count, seed)) => {
+                let band_w = self.map_size.w / players_count;
+                if count < 0 || band_w * self.map_size.h < count {
+                    return Err(format!("{} units per player do not fit \
+                        the map", count));
+                }
+                Scenario::generate(
+                    self.map_size, self.players.clone(), count, seed)
+            },
+            None => Scenario {
+                map_size: self.map_size,
+                players: self.players.clone(),
+                units: self.units.clone(),
+            },
+        };
+        try!(scenario.check(&self.rules));
+        Ok(scenario)
+    }
+
+    pub fn build(&self) -> Result<~Core, ~str> {
+        let scenario = try!(self.to_scenario());
+        let mut core = Core::with_rules(&scenario, self.rules.clone());
+        if self.is_consistency_check {
+            core.enable_consistency_check();
+        }
+        Ok(core)
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
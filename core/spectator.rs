@@ -0,0 +1,110 @@
+// See LICENSE file for copyright and license details.
+
+use types::{MBool, MInt, PlayerId, SubscriberId, Size2};
+use core::{
+    Core,
+    CoreInterface,
+    Command,
+    Event,
+    PlayerKind,
+    PlayerHuman,
+    PlayerAi,
+};
+use scenario::Scenario;
+use rules::Rules;
+use runner::Runner;
+use observer::DeliveryPoll;
+
+// Watches game of AI players: every AI command is made only when
+// frontend has shown all previous events, so the game goes
+// at the speed of visualization.
+pub struct SpectatorCore {
+    runner: Runner,
+    subscriber_id: SubscriberId,
+    view: Option<PlayerId>,
+    max_turns: MInt,
+}
+
+impl SpectatorCore {
+    // `view` is the player to follow, None means all players
+    pub fn new(
+        scenario: &Scenario,
+        view: Option<PlayerId>,
+        max_turns: MInt
+    ) -> Result<SpectatorCore, ~str> {
+        for kind in scenario.players.iter() {
+            match *kind {
+                PlayerAi(_) => {},
+                PlayerHuman => return Err(~"only AI games can be watched"),
+            }
+        }
+        match view {
+            Some(PlayerId(id)) if id < 0
+                || id >= scenario.players.len() as MInt =>
+            {
+                return Err(format!("can not view player {}, scenario \
+                    has {} players", id, scenario.players.len()));
+            },
+            _ => {},
+        }
+        let mut runner = Runner::new(Core::from_scenario(scenario));
+        let subscriber_id = runner.subscribe(view, DeliveryPoll);
+        Ok(SpectatorCore {
+            runner: runner,
+            subscriber_id: subscriber_id,
+            view: view,
+            max_turns: max_turns,
+        })
+    }
+
+    fn is_game_over(&self) -> MBool {
+        self.runner.winner().is_some()
+            || self.runner.turns() >= self.max_turns
+    }
+}
+
+impl CoreInterface for SpectatorCore {
+    fn map_size(&self) -> Size2<MInt> {
+        self.runner.map_size()
+    }
+
+    fn rules(&self) -> Rules {
+        self.runner.rules().clone()
+    }
+
+    fn players_count(&self) -> MInt {
+        self.runner.players_count()
+    }
+
+    // AIs are played by the runner, not by frontend
+    fn player_kind(&self, _: PlayerId) -> PlayerKind {
+        PlayerHuman
+    }
+
+    // Frontend keeps spectator`s view in the slot of followed player
+    fn player_id(&self) -> PlayerId {
+        self.view.unwrap_or(PlayerId(0))
+    }
+
+    fn is_player_turn(&self) -> MBool {
+        false
+    }
+
+    fn get_event(&mut self) -> Option<Event> {
+        let id = self.subscriber_id;
+        match self.runner.get_subscriber_event(id) {
+            Some(event) => return Some(event),
+            None => {},
+        }
+        if self.is_game_over() || !self.runner.is_ai_turn() {
+            return None;
+        }
+        let command = self.runner.get_ai_command();
+        self.runner.do_command(command);
+        self.runner.get_subscriber_event(id)
+    }
+
+    fn do_command(&mut self, _: Command) {}
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -0,0 +1,76 @@
+// See LICENSE file for copyright and license details.
+
+use types::{MInt, PlayerId};
+use core::{
+    Event,
+    EventMove,
+    EventEndTurn,
+    EventCreateUnit,
+    EventAttackUnit,
+    EventMoveOrder,
+};
+use game_state::GameState;
+
+// What happened since player`s previous turn
+pub struct TurnSummary {
+    pub player_id: PlayerId,
+    pub enemy_moves: MInt,
+    pub enemy_units_created: MInt,
+    pub units_lost: MInt,
+    pub enemy_units_lost: MInt,
+}
+
+impl TurnSummary {
+    // `state` is player`s state before `events` are applied
+    pub fn new(
+        state: &GameState,
+        player_id: PlayerId,
+        events: &[Event]
+    ) -> TurnSummary {
+        let mut summary = TurnSummary {
+            player_id: player_id,
+            enemy_moves: 0,
+            enemy_units_created: 0,
+            units_lost: 0,
+            enemy_units_lost: 0,
+        };
+        let mut state = state.clone();
+        for event in events.iter() {
+            match *event {
+                EventMove(unit_id, _) => {
+                    if state.units.get(&unit_id).player_id != player_id {
+                        summary.enemy_moves += 1;
+                    }
+                },
+                EventCreateUnit(_, _, owner_id) => {
+                    if owner_id != player_id {
+                        summary.enemy_units_created += 1;
+                    }
+                },
+                EventAttackUnit(_, defender_id) => {
+                    if state.units.get(&defender_id).player_id == player_id {
+                        summary.units_lost += 1;
+                    } else {
+                        summary.enemy_units_lost += 1;
+                    }
+                },
+                EventEndTurn(_, _) | EventMoveOrder(_, _) => {},
+            }
+            state.apply_event(event);
+        }
+        summary
+    }
+
+    pub fn to_lines(&self) -> Vec<~str> {
+        let PlayerId(id) = self.player_id;
+        vec!(
+            format!("player {}, it is your turn", id),
+            format!("enemy moves: {}", self.enemy_moves),
+            format!("enemy units created: {}", self.enemy_units_created),
+            format!("your units lost: {}", self.units_lost),
+            format!("enemy units destroyed: {}", self.enemy_units_lost),
+        )
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
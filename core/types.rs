@@ -3,7 +3,7 @@
 use cgmath::vector::Vec2;
 use std::cmp::{TotalOrd};
 
-#[deriving(Decodable)]
+#[deriving(Decodable, Clone)]
 pub struct Size2<T>{
     pub w: T,
     pub h: T,
@@ -13,13 +13,17 @@ pub type Point2<T> = Vec2<T>;
 
 pub type MBool = bool;
 pub type MInt = i32;
+pub type MFloat = f32;
 
-#[deriving(Ord, Eq, TotalEq, Hash)]
+#[deriving(Ord, TotalOrd, Eq, TotalEq, Hash)]
 pub struct PlayerId(pub MInt);
 
 #[deriving(Ord, TotalOrd, Eq, TotalEq, Hash)]
 pub struct UnitId(pub MInt);
 
+#[deriving(Ord, Eq, TotalEq, Hash)]
+pub struct SubscriberId(pub MInt);
+
 pub type MapPos = Vec2<MInt>;
 
 // vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
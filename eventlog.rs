@@ -0,0 +1,284 @@
+// See LICENSE file for copyright and license details.
+
+#![deny(non_camel_case_types)]
+#![deny(non_uppercase_statics)]
+#![deny(unnecessary_qualification)]
+#![deny(unnecessary_typecast)]
+
+extern crate native;
+extern crate getopts;
+extern crate core;
+
+use std::os;
+use std::cmp::max;
+use getopts::{optopt, optflag, getopts, usage, OptGroup};
+use core::core::{
+    Event,
+    EventMove,
+    EventEndTurn,
+    EventCreateUnit,
+    EventAttackUnit,
+    EventMoveOrder,
+};
+use core::types::{MBool, MInt, UnitId, PlayerId};
+use core::jsonl::{read_events, event_to_line};
+use core::replay::EventFormatter;
+
+static EVENT_TYPES: [&'static str, ..5] =
+    ["move", "end_turn", "create_unit", "attack", "move_order"];
+
+struct Filter {
+    player_id: Option<PlayerId>,
+    unit_id: Option<UnitId>,
+    turn: Option<MInt>,
+    event_type: Option<~str>,
+}
+
+struct Options {
+    paths: Vec<Path>,
+    filter: Filter,
+    context: uint,
+    is_json: MBool,
+}
+
+// One event of the log with what formatter knew about it
+struct Entry {
+    number: uint, // position of the event in the log, from 1
+    event: Event,
+    turn: MInt,
+    player_id: Option<PlayerId>,
+    text: ~str,
+    line: ~str,
+}
+
+fn get_opts() -> Vec<OptGroup> {
+    vec!(
+        optopt("p", "player", "only events made by this player", "ID"),
+        optopt("u", "unit", "only events about this unit", "ID"),
+        optopt("t", "turn", "only events of this turn", "N"),
+        optopt("", "type", "only events of this type: move, end_turn, \
+            create_unit, attack or move_order", "TYPE"),
+        optopt("c", "context", "events shown before the difference, \
+            default is 3", "N"),
+        optflag("j", "json", "print events as JSON Lines"),
+        optflag("h", "help", "print this help"),
+    )
+}
+
+fn parse_num<T: FromStr>(
+    name: &str,
+    value: Option<~str>,
+    default: T
+) -> Result<T, ~str> {
+    match value {
+        Some(value) => match from_str(value.as_slice()) {
+            Some(n) => Ok(n),
+            None => Err(format!("bad {}: '{}'", name, value)),
+        },
+        None => Ok(default),
+    }
+}
+
+fn parse_id(name: &str, value: Option<~str>) -> Result<Option<MInt>, ~str> {
+    match value {
+        Some(value) => Ok(Some(try!(parse_num(name, Some(value), 0)))),
+        None => Ok(None),
+    }
+}
+
+fn parse_type(value: Option<~str>) -> Result<Option<~str>, ~str> {
+    match value {
+        Some(value) => {
+            if !EVENT_TYPES.iter().any(|t| *t == value.as_slice()) {
+                return Err(format!("unknown event type: '{}', \
+                    expected one of: {}", value, EVENT_TYPES.connect(", ")));
+            }
+            Ok(Some(value))
+        },
+        None => Ok(None),
+    }
+}
+
+fn parse_options(args: &[~str]) -> Result<Option<Options>, ~str> {
+    let opts = get_opts();
+    let matches = match getopts(args.tail(), opts.as_slice()) {
+        Ok(matches) => matches,
+        Err(f) => return Err(f.to_err_msg()),
+    };
+    if matches.opt_present("h") {
+        let brief = format!("Usage: {} [options] <log.jsonl> [other.jsonl]\n\
+            \n\
+            Prints events of the log. With two logs prints the first \
+            event where they differ.", args[0]);
+        println!("{}", usage(brief.as_slice(), opts.as_slice()));
+        return Ok(None);
+    }
+    if matches.free.len() < 1 || matches.free.len() > 2 {
+        return Err(format!("expected one or two logs, see {} --help",
+            args[0]));
+    }
+    Ok(Some(Options {
+        paths: matches.free.iter().map(|path| Path::new(path.as_slice()))
+            .collect(),
+        filter: Filter {
+            player_id: try!(parse_id("player id", matches.opt_str("p")))
+                .map(|id| PlayerId(id)),
+            unit_id: try!(parse_id("unit id", matches.opt_str("u")))
+                .map(|id| UnitId(id)),
+            turn: try!(parse_id("turn", matches.opt_str("t"))),
+            event_type: try!(parse_type(matches.opt_str("type"))),
+        },
+        context: try!(parse_num("context", matches.opt_str("c"), 3u)),
+        is_json: matches.opt_present("j"),
+    }))
+}
+
+fn event_type(event: &Event) -> &'static str {
+    match *event {
+        EventMove(..) => "move",
+        EventEndTurn(..) => "end_turn",
+        EventCreateUnit(..) => "create_unit",
+        EventAttackUnit(..) => "attack",
+        EventMoveOrder(..) => "move_order",
+    }
+}
+
+fn event_units(event: &Event) -> Vec<UnitId> {
+    match *event {
+        EventMove(id, _) => vec!(id),
+        EventEndTurn(_, _) => Vec::new(),
+        EventCreateUnit(id, _, _) => vec!(id),
+        EventAttackUnit(attacker_id, defender_id) => {
+            vec!(attacker_id, defender_id)
+        },
+        EventMoveOrder(id, _) => vec!(id),
+    }
+}
+
+fn is_matching(filter: &Filter, entry: &Entry) -> MBool {
+    let is_player_ok = match filter.player_id {
+        Some(id) => entry.player_id == Some(id),
+        None => true,
+    };
+    let is_unit_ok = match filter.unit_id {
+        Some(id) => event_units(&entry.event).contains(&id),
+        None => true,
+    };
+    let is_turn_ok = match filter.turn {
+        Some(turn) => entry.turn == turn,
+        None => true,
+    };
+    let is_type_ok = match filter.event_type {
+        Some(ref name) => event_type(&entry.event) == name.as_slice(),
+        None => true,
+    };
+    is_player_ok && is_unit_ok && is_turn_ok && is_type_ok
+}
+
+// Whole log goes through formatter, so turns and owners of units
+// are right even if the filter skips most of the events
+fn read_entries(path: &Path, filter: &Filter) -> Result<Vec<Entry>, ~str> {
+    let events = try!(read_events(path));
+    let mut formatter = EventFormatter::new();
+    let mut entries = Vec::new();
+    for (i, event) in events.move_iter().enumerate() {
+        let turn = formatter.turn();
+        let player_id = formatter.player_of(&event);
+        let text = formatter.format(&event);
+        let line = event_to_line(&event);
+        let entry = Entry {
+            number: i + 1,
+            text: text,
+            line: line,
+            event: event,
+            turn: turn,
+            player_id: player_id,
+        };
+        if is_matching(filter, &entry) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+fn entry_to_str(entry: &Entry, is_json: MBool) -> ~str {
+    if is_json {
+        entry.line.clone()
+    } else {
+        format!("{}: {}", entry.number, entry.text)
+    }
+}
+
+fn show(options: &Options) -> Result<(), ~str> {
+    let entries = try!(read_entries(options.paths.get(0), &options.filter));
+    for entry in entries.iter() {
+        println!("{}", entry_to_str(entry, options.is_json));
+    }
+    Ok(())
+}
+
+// Returns true if logs are the same
+fn diff(options: &Options) -> Result<MBool, ~str> {
+    let a_path = options.paths.get(0);
+    let b_path = options.paths.get(1);
+    let a = try!(read_entries(a_path, &options.filter));
+    let b = try!(read_entries(b_path, &options.filter));
+    let mut i = 0;
+    while i < a.len() && i < b.len() && a.get(i).line == b.get(i).line {
+        i += 1;
+    }
+    if i == a.len() && i == b.len() {
+        println!("logs are the same: {} events", i);
+        return Ok(true);
+    }
+    println!("--- {}", a_path.display());
+    println!("+++ {}", b_path.display());
+    for entry in a.slice(max(i, options.context) - options.context, i)
+        .iter()
+    {
+        println!("  {}", entry_to_str(entry, options.is_json));
+    }
+    match a.as_slice().get(i) {
+        Some(entry) => println!("- {}", entry_to_str(entry, options.is_json)),
+        None => println!("- <end of log>"),
+    }
+    match b.as_slice().get(i) {
+        Some(entry) => println!("+ {}", entry_to_str(entry, options.is_json)),
+        None => println!("+ <end of log>"),
+    }
+    Ok(false)
+}
+
+fn main() {
+    let args = os::args();
+    let options = match parse_options(args.as_slice()) {
+        Ok(Some(options)) => options,
+        Ok(None) => return,
+        Err(e) => {
+            println!("Error: {}", e);
+            os::set_exit_status(1);
+            return;
+        },
+    };
+    let result = if options.paths.len() == 1 {
+        show(&options).map(|_| true)
+    } else {
+        diff(&options)
+    };
+    match result {
+        Ok(true) => {},
+        // Logs differ, like diff(1) does
+        Ok(false) => os::set_exit_status(1),
+        Err(e) => {
+            println!("Error: {}", e);
+            os::set_exit_status(2);
+        },
+    }
+}
+
+#[start]
+fn start(argc: int, argv: **u8) -> int {
+    native::start(argc, argv, main)
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -0,0 +1,272 @@
+// See LICENSE file for copyright and license details.
+
+#![deny(non_camel_case_types)]
+#![deny(non_uppercase_statics)]
+#![deny(unnecessary_qualification)]
+#![deny(unnecessary_typecast)]
+
+extern crate native;
+extern crate rand;
+extern crate cgmath;
+extern crate getopts;
+extern crate core;
+
+use std::os;
+use std::task::try;
+use rand::{Rng, SeedableRng, XorShiftRng};
+use cgmath::vector::Vec2;
+use getopts::{optopt, optflag, getopts, usage, OptGroup};
+use core::core::{
+    Command,
+    CommandMove,
+    CommandEndTurn,
+    CommandCreateUnit,
+    CommandAttackUnit,
+    CommandSetMoveOrder,
+    PlayerHuman,
+};
+use core::types::{MInt, UnitId, MapPos, Size2};
+use core::dir::Dir;
+use core::settings::GameSettings;
+use core::runner::Runner;
+
+static MAX_PATH_LEN: MInt = 4;
+static MAX_ORDER_PATH_LEN: MInt = 12;
+
+struct Options {
+    games: MInt,
+    seed: u32,
+    commands: MInt,
+    map_size: Size2<MInt>,
+    units_per_player: MInt,
+}
+
+// Result of one game
+struct GameRecord {
+    commands: MInt, // accepted by GameState::check_command
+    rejected: MInt,
+}
+
+fn get_opts() -> Vec<OptGroup> {
+    vec!(
+        optopt("n", "games", "number of games, default is 100", "N"),
+        optopt("s", "seed", "seed of the first game, default is 0", "SEED"),
+        optopt("c", "commands", "commands per game, default is 200", "N"),
+        optopt("", "size", "size of generated map, default is 9x6", "WxH"),
+        optopt("u", "units", "units per player, default is 3", "N"),
+        optflag("h", "help", "print this help"),
+    )
+}
+
+fn parse_num<T: FromStr>(
+    name: &str,
+    value: Option<~str>,
+    default: T
+) -> Result<T, ~str> {
+    match value {
+        Some(value) => match from_str(value.as_slice()) {
+            Some(n) => Ok(n),
+            None => Err(format!("bad {}: '{}'", name, value)),
+        },
+        None => Ok(default),
+    }
+}
+
+fn parse_size(value: Option<~str>) -> Result<Size2<MInt>, ~str> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(Size2{w: 9, h: 6}),
+    };
+    let mut parts = value.split('x');
+    let w = parts.next().and_then(|s| from_str(s));
+    let h = parts.next().and_then(|s| from_str(s));
+    match (w, h, parts.next()) {
+        (Some(w), Some(h), None) if w > 0 && h > 0 => Ok(Size2{w: w, h: h}),
+        _ => Err(format!("bad map size: '{}'", value)),
+    }
+}
+
+fn parse_options(args: &[~str]) -> Result<Option<Options>, ~str> {
+    let opts = get_opts();
+    let matches = match getopts(args.tail(), opts.as_slice()) {
+        Ok(matches) => matches,
+        Err(f) => return Err(f.to_err_msg()),
+    };
+    if matches.opt_present("h") {
+        let brief = format!("Usage: {} [options]", args[0]);
+        println!("{}", usage(brief.as_slice(), opts.as_slice()));
+        return Ok(None);
+    }
+    let options = Options {
+        games: try!(parse_num("games count", matches.opt_str("n"), 100)),
+        seed: try!(parse_num("seed", matches.opt_str("s"), 0)),
+        commands: try!(parse_num(
+            "commands count", matches.opt_str("c"), 200)),
+        map_size: try!(parse_size(matches.opt_str("size"))),
+        units_per_player: try!(parse_num(
+            "units count", matches.opt_str("u"), 3)),
+    };
+    if options.map_size.w / 2 * options.map_size.h
+        < options.units_per_player
+    {
+        return Err(~"map is too small for that many units");
+    }
+    Ok(Some(options))
+}
+
+fn random_pos(rng: &mut XorShiftRng, map_size: Size2<MInt>) -> MapPos {
+    Vec2 {
+        x: rng.gen_range(0, map_size.w),
+        y: rng.gen_range(0, map_size.h),
+    }
+}
+
+// Random walk: may leave the map or end on a full tile,
+// such paths are rejected by check_command
+fn random_path(
+    rng: &mut XorShiftRng,
+    from: MapPos,
+    max_len: MInt
+) -> Vec<MapPos> {
+    let mut path = vec!(from);
+    for _ in range(0, rng.gen_range(1, max_len + 1)) {
+        let dir = Dir::from_int(rng.gen_range(0, 6));
+        let pos = Dir::get_neighbour_pos(*path.last().unwrap(), dir);
+        path.push(pos);
+    }
+    path
+}
+
+fn random_id(rng: &mut XorShiftRng, ids: &[UnitId]) -> UnitId {
+    ids[rng.gen_range(0, ids.len())]
+}
+
+// Units are sorted because HashMap`s order differs from run to run
+fn random_command(rng: &mut XorShiftRng, runner: &Runner) -> Command {
+    let player_id = runner.player_id();
+    let state = runner.state(player_id);
+    let mut all_ids: Vec<UnitId> = state.units.keys()
+        .map(|id| *id).collect();
+    all_ids.sort();
+    let own_ids: Vec<UnitId> = all_ids.iter()
+        .filter(|id| state.units.get(*id).player_id == player_id)
+        .map(|id| *id).collect();
+    let kind = rng.gen_range(0, 10);
+    if kind == 0 {
+        CommandEndTurn
+    } else if kind == 1 || own_ids.len() == 0 {
+        CommandCreateUnit(random_pos(rng, runner.map_size()))
+    } else if kind <= 3 {
+        let attacker_id = random_id(rng, own_ids.as_slice());
+        let defender_id = random_id(rng, all_ids.as_slice());
+        CommandAttackUnit(attacker_id, defender_id)
+    } else if kind == 4 {
+        let unit_id = random_id(rng, own_ids.as_slice());
+        let pos = state.units.get(&unit_id).pos;
+        let path = random_path(rng, pos, MAX_ORDER_PATH_LEN);
+        CommandSetMoveOrder(unit_id, path)
+    } else {
+        let unit_id = random_id(rng, own_ids.as_slice());
+        let pos = state.units.get(&unit_id).pos;
+        CommandMove(unit_id, random_path(rng, pos, MAX_PATH_LEN))
+    }
+}
+
+fn play_game(
+    seed: u32,
+    map_size: Size2<MInt>,
+    units_per_player: MInt,
+    commands_count: MInt
+) -> Result<GameRecord, ~str> {
+    let mut rng: XorShiftRng = SeedableRng::from_seed(
+        [0x2f6b3a91 ^ seed, 0x5c1d8e47, 0x93a7f015, 0x0e4b62cd]);
+    let core = try!(GameSettings::new(map_size)
+        .players(vec!(PlayerHuman, PlayerHuman))
+        .random_units(units_per_player, seed)
+        .consistency_check()
+        .build());
+    let mut runner = Runner::new(core);
+    let mut record = GameRecord {
+        commands: 0,
+        rejected: 0,
+    };
+    while record.commands < commands_count && runner.winner().is_none() {
+        let command = random_command(&mut rng, &runner);
+        let player_id = runner.player_id();
+        let result = runner.state(player_id).check_command(
+            runner.map_size(), player_id, &command);
+        if result.is_err() {
+            record.rejected += 1;
+            continue;
+        }
+        runner.do_command(command);
+        record.commands += 1;
+        match runner.divergences().head() {
+            Some(divergence) => {
+                return Err(format!("command {}: {}",
+                    record.commands, divergence.to_str()));
+            },
+            None => {},
+        }
+    }
+    Ok(record)
+}
+
+fn run(options: &Options) -> Result<(), ~str> {
+    let mut commands = 0;
+    let mut rejected = 0;
+    let mut failures = 0;
+    for game in range(0, options.games) {
+        let seed = options.seed + game as u32;
+        let map_size = options.map_size;
+        let units_per_player = options.units_per_player;
+        let commands_count = options.commands;
+        // Panic in Core or GameState is a failure too
+        let result = try(proc() {
+            play_game(seed, map_size, units_per_player, commands_count)
+        });
+        match result {
+            Ok(Ok(record)) => {
+                commands += record.commands;
+                rejected += record.rejected;
+            },
+            Ok(Err(e)) => {
+                println!("game {} (seed {}): {}", game, seed, e);
+                failures += 1;
+            },
+            Err(_) => {
+                println!("game {} (seed {}): panicked", game, seed);
+                failures += 1;
+            },
+        }
+    }
+    println!("games: {}, commands: {}, rejected: {}, failures: {}",
+        options.games, commands, rejected, failures);
+    if failures != 0 {
+        return Err(format!("{} of {} games failed", failures, options.games));
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = os::args();
+    let result = match parse_options(args.as_slice()) {
+        Ok(Some(options)) => run(&options),
+        Ok(None) => Ok(()),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => {},
+        Err(e) => {
+            println!("Error: {}", e);
+            os::set_exit_status(1);
+        },
+    }
+}
+
+#[start]
+fn start(argc: int, argv: **u8) -> int {
+    native::start(argc, argv, main)
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -0,0 +1,270 @@
+// See LICENSE file for copyright and license details.
+
+#![deny(non_camel_case_types)]
+#![deny(non_uppercase_statics)]
+#![deny(unnecessary_qualification)]
+#![deny(unnecessary_typecast)]
+
+extern crate native;
+extern crate cgmath;
+extern crate getopts;
+extern crate core;
+
+use std::os;
+use cgmath::vector::Vec2;
+use getopts::{optopt, optflag, getopts, usage, OptGroup};
+use core::core::{
+    Command,
+    CommandMove,
+    CommandEndTurn,
+    CommandCreateUnit,
+    CommandAttackUnit,
+    CommandSetMoveOrder,
+};
+use core::types::{MInt, UnitId, PlayerId, MapPos};
+use core::misc::try_read_file;
+use core::runner::Runner;
+use core::scenario::Scenario;
+use core::rules::Rules;
+use core::settings::GameSettings;
+use core::replay::event_to_str;
+use core::observer::DeliveryCallback;
+use core::jsonl::JsonlWriter;
+
+struct Options {
+    scenario_path: ~str,
+    script_path: Option<~str>,
+    events_path: Option<Path>,
+}
+
+fn get_opts() -> Vec<OptGroup> {
+    vec!(
+        optopt("e", "events", "write all events to FILE as JSON Lines",
+            "FILE"),
+        optflag("h", "help", "print this help"),
+    )
+}
+
+fn parse_options(args: &[~str]) -> Result<Option<Options>, ~str> {
+    let opts = get_opts();
+    let matches = match getopts(args.tail(), opts.as_slice()) {
+        Ok(matches) => matches,
+        Err(f) => return Err(f.to_err_msg()),
+    };
+    if matches.opt_present("h") {
+        let brief = format!("Usage: {} [options] <scenario.json> [script]",
+            args[0]);
+        println!("{}", usage(brief.as_slice(), opts.as_slice()));
+        return Ok(None);
+    }
+    let free = matches.free.as_slice();
+    if free.len() < 1 || free.len() > 2 {
+        return Err(format!("expected <scenario.json> [script], \
+            see {} --help", args[0]));
+    }
+    Ok(Some(Options {
+        scenario_path: free[0].clone(),
+        script_path: free.get(1).map(|path| path.clone()),
+        events_path: matches.opt_str("e").map(|path| Path::new(path)),
+    }))
+}
+
+fn parse_unit_id(word: &str) -> Result<UnitId, ~str> {
+    match from_str(word) {
+        Some(id) => Ok(UnitId(id)),
+        None => Err(format!("bad unit id: '{}'", word)),
+    }
+}
+
+fn parse_pos(word: &str) -> Result<MapPos, ~str> {
+    let mut coords = word.split(',');
+    let x = coords.next().and_then(|s| from_str(s));
+    let y = coords.next().and_then(|s| from_str(s));
+    match (x, y, coords.next()) {
+        (Some(x), Some(y), None) => Ok(Vec2{x: x, y: y}),
+        _ => Err(format!("bad position: '{}'", word)),
+    }
+}
+
+// Script format, one command per line:
+//
+// move <unit_id> <x>,<y> <x>,<y> ...
+// order <unit_id> <x>,<y> <x>,<y> ...
+// attack <attacker_id> <defender_id>
+// create <x>,<y>
+// end_turn
+fn parse_command(line: &str) -> Result<Option<Command>, ~str> {
+    let mut words = line.words();
+    let tag = match words.next() {
+        Some(tag) if !tag.starts_with("#") => tag,
+        _ => return Ok(None),
+    };
+    let args: Vec<&str> = words.collect();
+    let command = match (tag, args.len()) {
+        ("end_turn", 0) => CommandEndTurn,
+        ("create", 1) => {
+            CommandCreateUnit(try!(parse_pos(*args.get(0))))
+        },
+        ("attack", 2) => {
+            let attacker_id = try!(parse_unit_id(*args.get(0)));
+            let defender_id = try!(parse_unit_id(*args.get(1)));
+            CommandAttackUnit(attacker_id, defender_id)
+        },
+        ("move", n) | ("order", n) if n >= 3 => {
+            let unit_id = try!(parse_unit_id(*args.get(0)));
+            let mut path = Vec::new();
+            for word in args.slice_from(1).iter() {
+                path.push(try!(parse_pos(*word)));
+            }
+            if tag == "move" {
+                CommandMove(unit_id, path)
+            } else {
+                CommandSetMoveOrder(unit_id, path)
+            }
+        },
+        _ => return Err(format!("bad command: '{}'", line)),
+    };
+    Ok(Some(command))
+}
+
+// Game that has no winner after this many turns is a draw
+static MAX_TURNS: MInt = 1000;
+
+struct Script {
+    path: Path,
+    lines: Vec<~str>,
+    line_index: uint,
+}
+
+impl Script {
+    fn new(path: &str) -> Result<Script, ~str> {
+        let path = Path::new(path);
+        let text = match try_read_file(&path) {
+            Ok(text) => text,
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        Ok(Script {
+            lines: text.lines().map(|line| line.to_owned()).collect(),
+            path: path,
+            line_index: 0,
+        })
+    }
+
+    fn error(&self, message: &str) -> ~str {
+        format!("{}:{}: {}", self.path.display(), self.line_index, message)
+    }
+
+    fn next_command(&mut self) -> Result<Option<Command>, ~str> {
+        while self.line_index < self.lines.len() {
+            self.line_index += 1;
+            let line = self.lines.get(self.line_index - 1).as_slice();
+            match parse_command(line) {
+                Ok(Some(command)) => return Ok(Some(command)),
+                Ok(None) => {},
+                Err(e) => return Err(self.error(e.as_slice())),
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn print_new_events(runner: &Runner, printed_events: &mut uint) {
+    let log = runner.log();
+    for event in log.slice_from(*printed_events).iter() {
+        println!("{}", event_to_str(event));
+    }
+    *printed_events = log.len();
+}
+
+fn run(options: &Options) -> Result<(), ~str> {
+    // Bad script is reported before event log is created
+    let mut script = match options.script_path {
+        Some(ref path) => Some(try!(Script::new(path.as_slice()))),
+        None => None,
+    };
+    let path = options.scenario_path.as_slice();
+    let rules = Rules::new();
+    let scenario = match Scenario::from_config(path, &rules) {
+        Ok(scenario) => scenario,
+        Err(e) => return Err(e.to_str()),
+    };
+    let core = try!(GameSettings::from_scenario(&scenario)
+        .rules(rules)
+        .build());
+    let mut runner = Runner::new(core);
+    let (log_errors_sender, log_errors) = channel();
+    match options.events_path {
+        Some(ref path) => {
+            let mut writer = match JsonlWriter::new(path) {
+                Ok(writer) => writer,
+                Err(e) => return Err(format!("can not create {}: {}",
+                    path.display(), e)),
+            };
+            writer.set_errors(log_errors_sender);
+            runner.subscribe(None, DeliveryCallback(~writer));
+        },
+        None => {},
+    }
+    let mut printed_events = 0;
+    print_new_events(&runner, &mut printed_events);
+    while runner.winner().is_none() && runner.turns() < MAX_TURNS {
+        let command = if runner.is_ai_turn() {
+            runner.get_ai_command()
+        } else {
+            let script = match script {
+                Some(ref mut script) => script,
+                None => return Err(~"script is required for human players"),
+            };
+            let command = match try!(script.next_command()) {
+                Some(command) => command,
+                None => break,
+            };
+            let player_id = runner.player_id();
+            let state = runner.state(player_id);
+            let map_size = runner.map_size();
+            match state.check_command(map_size, player_id, &command) {
+                Ok(()) => command,
+                Err(e) => return Err(script.error(e.as_slice())),
+            }
+        };
+        runner.do_command(command);
+        print_new_events(&runner, &mut printed_events);
+        match log_errors.try_recv() {
+            Ok(e) => println!("Can not write event log, \
+                it is stopped: {}", e),
+            Err(_) => {},
+        }
+    }
+    match runner.winner() {
+        Some(PlayerId(id)) => println!("result: player {} won", id),
+        None => println!("result: no winner"),
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = os::args();
+    let options = match parse_options(args.as_slice()) {
+        Ok(Some(options)) => options,
+        Ok(None) => return,
+        Err(e) => {
+            println!("Error: {}", e);
+            os::set_exit_status(1);
+            return;
+        },
+    };
+    match run(&options) {
+        Ok(()) => {},
+        Err(e) => {
+            println!("Error: {}", e);
+            os::set_exit_status(1);
+        },
+    }
+}
+
+#[start]
+fn start(argc: int, argv: **u8) -> int {
+    native::start(argc, argv, main)
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
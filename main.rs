@@ -14,14 +14,389 @@ extern crate cgmath;
 extern crate glfw;
 extern crate gl;
 extern crate stb_image;
+extern crate getopts;
+extern crate core;
 
-use visualizer::visualizer::Visualizer;
+use std::os;
+use std::io::net::ip::SocketAddr;
+use getopts::{optopt, optflag, optmulti, getopts, usage, OptGroup, Matches};
+use core::core::{Core, CoreInterface, PlayerKind, PlayerHuman};
+use core::types::{MBool, MInt, PlayerId, Size2};
+use core::net::Client;
+use core::scenario::Scenario;
+use core::rules::Rules;
+use core::pbem::{TurnFile, PbemCore};
+use core::spectator::SpectatorCore;
+use core::ai::AI_NAMES;
+use core::locator::Locator;
+use visualizer::visualizer::{Visualizer, load_config};
 
-mod core;
 mod visualizer;
 
+// Watched game that has no winner after this many turns is a draw
+static SPECTATOR_MAX_TURNS: MInt = 1000;
+
+enum Mode {
+    ModeLocal,
+    ModeConnect(SocketAddr),
+    ModePbemNew(Path), // out turn file
+    ModePbem(Path, Path, Option<Path>), // in, out and previous files
+    ModeSpectate(Option<PlayerId>),
+    ModeLoad(Path),
+}
+
+struct Options {
+    mode: Mode,
+    is_hot_seat: MBool,
+    core_config: ~str,
+    visualizer_config: ~str,
+    locator: Locator,
+    scenario_path: Option<~str>,
+    seed: Option<u32>,
+    map_size: Size2<MInt>,
+    units_per_player: MInt,
+    players: Option<Vec<PlayerKind>>,
+    overrides: Vec<~str>, // `name=value` pairs for both configs
+}
+
+fn get_opts() -> Vec<OptGroup> {
+    vec!(
+        optopt("c", "config", "core config, default is conf_core.json",
+            "FILE"),
+        optopt("", "visualizer-config",
+            "visualizer config, default is conf_visualizer.json", "FILE"),
+        optopt("d", "data", "data directory, default is data", "DIR"),
+        optmulti("", "mod", "directory with files that override game's \
+            ones, can be repeated", "DIR"),
+        optopt("m", "scenario", "scenario file to play instead of \
+            the core config", "FILE"),
+        optopt("s", "seed", "play random scenario generated with this seed",
+            "SEED"),
+        optopt("", "map-size", "size of generated map, default is 9x6",
+            "WxH"),
+        optopt("u", "units", "units per player of generated map, \
+            default is 3", "N"),
+        optopt("p", "players", "comma separated players: human or \
+            AI name, for example human,greedy", "LIST"),
+        optopt("", "window", "window size, default is taken \
+            from the visualizer config", "WxH"),
+        optflag("", "hot-seat", "players share one screen"),
+        optopt("", "connect", "join network game", "HOST:PORT"),
+        optopt("", "pbem-new", "start play-by-email game and write \
+            the first turn to FILE", "FILE"),
+        optopt("", "pbem", "import play-by-email turn file", "FILE"),
+        optopt("o", "out", "turn file to export, required by --pbem",
+            "FILE"),
+        optopt("", "previous", "previously sent turn file that \
+            the imported one must continue", "FILE"),
+        optflag("", "spectate", "watch a game of AI players"),
+        optopt("", "view", "spectator sees only events of this player",
+            "PLAYER"),
+        optopt("", "load", "continue saved game from a turn file", "FILE"),
+        optmulti("", "set", "config override, can be repeated",
+            "NAME=VALUE"),
+        optflag("h", "help", "print this help"),
+    )
+}
+
+fn parse_num<T: FromStr>(
+    name: &str,
+    value: Option<~str>,
+    default: T
+) -> Result<T, ~str> {
+    match value {
+        Some(value) => match from_str(value.as_slice()) {
+            Some(n) => Ok(n),
+            None => Err(format!("bad {}: '{}'", name, value)),
+        },
+        None => Ok(default),
+    }
+}
+
+fn parse_size(value: &str) -> Result<Size2<MInt>, ~str> {
+    let mut parts = value.split('x');
+    let w = parts.next().and_then(|s| from_str(s));
+    let h = parts.next().and_then(|s| from_str(s));
+    match (w, h, parts.next()) {
+        (Some(w), Some(h), None) if w > 0 && h > 0 => Ok(Size2{w: w, h: h}),
+        _ => Err(format!("bad size: '{}'", value)),
+    }
+}
+
+fn parse_players(value: &str) -> Result<Vec<PlayerKind>, ~str> {
+    let mut players = Vec::new();
+    for name in value.split(',') {
+        if name != "human" && !AI_NAMES.iter().any(|ai| *ai == name) {
+            return Err(format!("unknown player: '{}', expected human \
+                or one of: {}", name, AI_NAMES.connect(", ")));
+        }
+        players.push(PlayerKind::from_str(name));
+    }
+    if players.len() < 2 {
+        return Err(~"at least two players are required");
+    }
+    // Visualizer has unit markers only for two players
+    if players.len() > 2 {
+        return Err(~"at most two players are supported");
+    }
+    Ok(players)
+}
+
+fn parse_mode(matches: &Matches) -> Result<Mode, ~str> {
+    let modes = ["connect", "pbem-new", "pbem", "spectate", "load"];
+    let present: Vec<&str> = modes.iter()
+        .map(|name| *name)
+        .filter(|name| matches.opt_present(*name))
+        .collect();
+    if present.len() > 1 {
+        return Err(format!("--{} and --{} can not be used together",
+            *present.get(0), *present.get(1)));
+    }
+    if matches.opt_present("view") && !matches.opt_present("spectate") {
+        return Err(~"--view requires --spectate");
+    }
+    if !matches.opt_present("pbem") {
+        if matches.opt_present("o") || matches.opt_present("previous") {
+            return Err(~"--out and --previous require --pbem");
+        }
+    }
+    let path = |name: &str| Path::new(matches.opt_str(name).unwrap());
+    let mode = if matches.opt_present("connect") {
+        let value = matches.opt_str("connect").unwrap();
+        match from_str(value.as_slice()) {
+            Some(addr) => ModeConnect(addr),
+            None => return Err(format!("bad address: '{}'", value)),
+        }
+    } else if matches.opt_present("pbem-new") {
+        ModePbemNew(path("pbem-new"))
+    } else if matches.opt_present("pbem") {
+        if !matches.opt_present("o") {
+            return Err(~"--pbem requires --out");
+        }
+        let previous = matches.opt_str("previous").map(|p| Path::new(p));
+        ModePbem(path("pbem"), path("o"), previous)
+    } else if matches.opt_present("spectate") {
+        let view: Option<MInt> = match matches.opt_str("view") {
+            Some(value) => Some(try!(parse_num("player", Some(value), 0))),
+            None => None,
+        };
+        match view {
+            Some(id) if id < 0 => {
+                return Err(format!("bad player: '{}'", id));
+            },
+            _ => {},
+        }
+        ModeSpectate(view.map(|id| PlayerId(id)))
+    } else if matches.opt_present("load") {
+        ModeLoad(path("load"))
+    } else {
+        ModeLocal
+    };
+    Ok(mode)
+}
+
+fn parse_options(args: &[~str]) -> Result<Option<Options>, ~str> {
+    let opts = get_opts();
+    let matches = match getopts(args.tail(), opts.as_slice()) {
+        Ok(matches) => matches,
+        Err(f) => return Err(f.to_err_msg()),
+    };
+    if matches.opt_present("h") {
+        let brief = format!("Usage: {} [options]", args[0]);
+        println!("{}", usage(brief.as_slice(), opts.as_slice()));
+        return Ok(None);
+    }
+    if matches.free.len() != 0 {
+        return Err(format!("unexpected argument: '{}', \
+            see --help", *matches.free.get(0)));
+    }
+    let mode = try!(parse_mode(&matches));
+    let is_hot_seat = matches.opt_present("hot-seat");
+    let is_local = match mode {
+        ModeLocal | ModeLoad(_) => true,
+        _ => false,
+    };
+    if is_hot_seat && !is_local {
+        return Err(~"--hot-seat works only with local and loaded games");
+    }
+    if matches.opt_present("s") && matches.opt_present("m") {
+        return Err(~"--seed and --scenario can not be used together");
+    }
+    let players = match matches.opt_str("p") {
+        Some(value) => Some(try!(parse_players(value.as_slice()))),
+        None => None,
+    };
+    let map_size = match matches.opt_str("map-size") {
+        Some(value) => try!(parse_size(value.as_slice())),
+        None => Size2{w: 9, h: 6},
+    };
+    let mods: Vec<Path> = matches.opt_strs("mod").iter()
+        .map(|dir| Path::new(dir.as_slice()))
+        .collect();
+    let mut overrides = matches.opt_strs("set");
+    match matches.opt_str("window") {
+        Some(value) => {
+            let size = try!(parse_size(value.as_slice()));
+            overrides.push(format!(
+                "screen_size=\\{\"w\": {}, \"h\": {}\\}", size.w, size.h));
+        },
+        None => {},
+    }
+    Ok(Some(Options {
+        mode: mode,
+        is_hot_seat: is_hot_seat,
+        core_config: matches.opt_str("c").unwrap_or(~"conf_core.json"),
+        visualizer_config: matches.opt_str("visualizer-config")
+            .unwrap_or(~"conf_visualizer.json"),
+        locator: Locator::with_mods(
+            &Path::new(matches.opt_str("d").unwrap_or(~"data")),
+            mods.as_slice()),
+        scenario_path: matches.opt_str("m"),
+        seed: match matches.opt_str("s") {
+            Some(value) => Some(try!(parse_num("seed", Some(value), 0))),
+            None => None,
+        },
+        map_size: map_size,
+        units_per_player: try!(parse_num(
+            "units count", matches.opt_str("u"), 3)),
+        players: players,
+        overrides: overrides,
+    }))
+}
+
+fn generate_scenario(
+    options: &Options,
+    seed: u32,
+    rules: &Rules
+) -> Result<Scenario, ~str> {
+    let players = match options.players {
+        Some(ref players) => players.clone(),
+        None => vec!(PlayerHuman, PlayerKind::from_str("greedy")),
+    };
+    if options.units_per_player < 0 {
+        return Err(format!("bad units count: '{}'",
+            options.units_per_player));
+    }
+    let map_size = options.map_size;
+    if map_size.w / (players.len() as MInt) * map_size.h
+        < options.units_per_player
+    {
+        return Err(~"map is too small for that many units");
+    }
+    let scenario = Scenario::generate(
+        map_size, players, options.units_per_player, seed);
+    try!(scenario.check(rules));
+    Ok(scenario)
+}
+
+// Generated, explicit or core config`s scenario,
+// with players replaced by --players
+fn get_scenario(
+    options: &Options,
+    rules: &Rules
+) -> Result<Scenario, ~str> {
+    match options.seed {
+        Some(seed) => return generate_scenario(options, seed, rules),
+        None => {},
+    }
+    let result = match options.scenario_path {
+        Some(ref name) => {
+            let path = try!(options.locator.find_str(name.as_slice()));
+            Scenario::from_config(path.as_slice(), rules)
+        },
+        None => Scenario::from_layered_config(
+            options.core_config.as_slice(),
+            options.overrides.as_slice(),
+            rules,
+        ),
+    };
+    let mut scenario = match result {
+        Ok(scenario) => scenario,
+        Err(e) => return Err(e.to_str()),
+    };
+    match options.players {
+        Some(ref players) => {
+            if players.len() != scenario.players.len() {
+                return Err(format!("scenario has {} players, \
+                    but {} given", scenario.players.len(), players.len()));
+            }
+            scenario.players = players.clone();
+        },
+        None => {},
+    }
+    Ok(scenario)
+}
+
+fn get_core(options: &Options) -> Result<~CoreInterface, ~str> {
+    let rules = Rules::new();
+    let core = match options.mode {
+        ModeLocal => {
+            let scenario = try!(get_scenario(options, &rules));
+            Core::with_rules(&scenario, rules.clone()) as ~CoreInterface
+        },
+        ModeConnect(addr) => {
+            ~try!(Client::connect(addr, None)) as ~CoreInterface
+        },
+        ModePbemNew(ref out_path) => {
+            let file = TurnFile::new(try!(get_scenario(options, &rules)));
+            ~try!(PbemCore::new(file, out_path.clone())) as ~CoreInterface
+        },
+        ModePbem(ref in_path, ref out_path, ref previous_path) => {
+            let file = try!(TurnFile::load(in_path));
+            match *previous_path {
+                Some(ref path) => {
+                    let previous = try!(TurnFile::load(path));
+                    try!(file.check_continues(&previous));
+                },
+                None => {},
+            }
+            ~try!(PbemCore::new(file, out_path.clone())) as ~CoreInterface
+        },
+        ModeSpectate(view) => {
+            let scenario = try!(get_scenario(options, &rules));
+            ~try!(SpectatorCore::new(&scenario, view, SPECTATOR_MAX_TURNS))
+                as ~CoreInterface
+        },
+        ModeLoad(ref path) => {
+            let file = try!(TurnFile::load(path));
+            let _ = try!(file.check_turns());
+            file.to_core() as ~CoreInterface
+        },
+    };
+    Ok(core)
+}
+
+fn get_visualizer(options: &Options) -> Result<~Visualizer, ~str> {
+    let core = try!(get_core(options));
+    let config = match load_config(
+        options.visualizer_config.as_slice(), options.overrides.as_slice())
+    {
+        Ok(config) => config,
+        Err(e) => return Err(e.to_str()),
+    };
+    Visualizer::new(core, options.is_hot_seat, &config, &options.locator)
+}
+
 fn main() {
-    let mut visualizer = Visualizer::new();
+    let args = os::args();
+    let options = match parse_options(args.as_slice()) {
+        Ok(Some(options)) => options,
+        Ok(None) => return,
+        Err(e) => {
+            println!("Error: {}", e);
+            println!("Try '{} --help' for usage", args[0]);
+            os::set_exit_status(1);
+            return;
+        },
+    };
+    let mut visualizer = match get_visualizer(&options) {
+        Ok(visualizer) => visualizer,
+        Err(e) => {
+            println!("Error: {}", e);
+            os::set_exit_status(1);
+            return;
+        },
+    };
     while visualizer.is_running() {
         visualizer.tick();
     }
@@ -0,0 +1,380 @@
+// See LICENSE file for copyright and license details.
+
+#![deny(non_camel_case_types)]
+#![deny(non_uppercase_statics)]
+#![deny(unnecessary_qualification)]
+#![deny(unnecessary_typecast)]
+
+extern crate native;
+extern crate getopts;
+extern crate core;
+
+use std::os;
+use std::io::BufferedReader;
+use std::io::net::ip::{SocketAddr, Ipv4Addr};
+use std::io::net::tcp::TcpStream;
+use std::io::timer::sleep;
+use getopts::{optopt, optflag, getopts, usage, OptGroup};
+use core::core::{CoreInterface, Event, EventEndTurn, PlayerHuman};
+use core::types::{MBool, MInt, PlayerId, Size2};
+use core::game_state::GameState;
+use core::scenario::Scenario;
+use core::rules::Rules;
+use core::settings::GameSettings;
+use core::ai::{Ai, AI_NAMES, new_ai};
+use core::net::{
+    PROTOCOL_VERSION,
+    Client,
+    ClientHello,
+    ServerError,
+    client_message_to_str,
+    str_to_server_message,
+};
+use core::server::{
+    Server,
+    ServerStatus,
+    StatusPlayerConnected,
+    StatusPlayerDisconnected,
+};
+
+static RECONNECT_ATTEMPTS: MInt = 10;
+static RECONNECT_DELAY_MS: u64 = 100;
+
+struct Options {
+    listen_addr: SocketAddr,
+    scenario_path: ~str,
+    max_turns: MInt,
+    connect_addr: Option<SocketAddr>,
+    ai_name: ~str,
+    player_id: Option<PlayerId>,
+    is_selftest: MBool,
+}
+
+fn get_opts() -> Vec<OptGroup> {
+    vec!(
+        optopt("l", "listen", "address to listen on, default is \
+            0.0.0.0:7777", "ADDR"),
+        optopt("m", "map", "scenario, default is conf_core.json", "FILE"),
+        optopt("t", "max-turns", "turns before draw, default is 1000", "N"),
+        optopt("c", "connect", "join server as AI player", "ADDR"),
+        optopt("a", "ai", "AI strategy of client, default is greedy",
+            "NAME"),
+        optopt("p", "player", "player slot to take back after disconnect",
+            "N"),
+        optflag("", "selftest", "play AI clients against each other \
+            over loopback and check the result"),
+        optflag("h", "help", "print this help"),
+    )
+}
+
+fn parse_addr(value: Option<~str>) -> Result<Option<SocketAddr>, ~str> {
+    match value {
+        Some(value) => match from_str(value.as_slice()) {
+            Some(addr) => Ok(Some(addr)),
+            None => Err(format!("bad address: '{}'", value)),
+        },
+        None => Ok(None),
+    }
+}
+
+fn parse_num<T: FromStr>(
+    name: &str,
+    value: Option<~str>,
+    default: T
+) -> Result<T, ~str> {
+    match value {
+        Some(value) => match from_str(value.as_slice()) {
+            Some(n) => Ok(n),
+            None => Err(format!("bad {}: '{}'", name, value)),
+        },
+        None => Ok(default),
+    }
+}
+
+fn parse_ai(value: Option<~str>) -> Result<~str, ~str> {
+    let name = value.unwrap_or(~"greedy");
+    if !AI_NAMES.iter().any(|ai_name| *ai_name == name.as_slice()) {
+        return Err(format!("unknown AI: '{}', expected one of: {}",
+            name, AI_NAMES.connect(", ")));
+    }
+    Ok(name)
+}
+
+fn parse_options(args: &[~str]) -> Result<Option<Options>, ~str> {
+    let opts = get_opts();
+    let matches = match getopts(args.tail(), opts.as_slice()) {
+        Ok(matches) => matches,
+        Err(f) => return Err(f.to_err_msg()),
+    };
+    if matches.opt_present("h") {
+        let brief = format!("Usage: {} [options]", args[0]);
+        println!("{}", usage(brief.as_slice(), opts.as_slice()));
+        return Ok(None);
+    }
+    let default_addr = SocketAddr{ip: Ipv4Addr(0, 0, 0, 0), port: 7777};
+    let player_id = match matches.opt_str("p") {
+        Some(n) => Some(PlayerId(try!(parse_num("player", Some(n), 0)))),
+        None => None,
+    };
+    Ok(Some(Options {
+        listen_addr: try!(parse_addr(matches.opt_str("l")))
+            .unwrap_or(default_addr),
+        scenario_path: matches.opt_str("m").unwrap_or(~"conf_core.json"),
+        max_turns: try!(parse_num("turns limit", matches.opt_str("t"), 1000)),
+        connect_addr: try!(parse_addr(matches.opt_str("c"))),
+        ai_name: try!(parse_ai(matches.opt_str("a"))),
+        player_id: player_id,
+        is_selftest: matches.opt_present("selftest"),
+    }))
+}
+
+// Network player driven by AI
+struct Bot {
+    client: Client,
+    ai: ~Ai,
+    state: GameState,
+    events: Vec<Event>,
+    is_waiting: MBool, // all commands of current turn are sent
+}
+
+impl Bot {
+    fn new(client: Client, ai_name: &str) -> Result<Bot, ~str> {
+        let ai = try!(new_ai(
+            ai_name, client.player_id(), client.map_size()));
+        let state = GameState::new(client.rules());
+        Ok(Bot {
+            client: client,
+            ai: ai,
+            state: state,
+            events: Vec::new(),
+            is_waiting: false,
+        })
+    }
+
+    fn apply_event(&mut self, event: Event) {
+        match event {
+            EventEndTurn(old_id, _) if old_id == self.client.player_id() => {
+                self.is_waiting = false;
+            },
+            _ => {},
+        }
+        self.state.apply_event(&event);
+        self.events.push(event);
+    }
+
+    // Plays until the end of game or until `turns_limit` turns are played
+    fn play(&mut self, turns_limit: Option<MInt>) -> Result<(), ~str> {
+        let mut turns = 0;
+        loop {
+            loop {
+                match self.client.get_event() {
+                    Some(event) => self.apply_event(event),
+                    None => break,
+                }
+            }
+            match self.client.take_error() {
+                Some(e) => return Err(format!("server error: {}", e)),
+                None => {},
+            }
+            if self.client.is_game_over() {
+                return Ok(());
+            }
+            if self.client.is_player_turn() && !self.is_waiting {
+                if turns_limit == Some(turns) {
+                    return Ok(());
+                }
+                let commands = self.ai.take_turn(
+                    &self.state, self.events.as_slice());
+                self.events.clear();
+                for command in commands.move_iter() {
+                    self.client.do_command(command);
+                }
+                self.is_waiting = true;
+                turns += 1;
+            }
+            match self.client.wait_event() {
+                Some(event) => self.apply_event(event),
+                None if !self.client.is_game_over() => {
+                    return Err(~"connection to server is lost");
+                },
+                None => {},
+            }
+        }
+    }
+}
+
+// Server frees player`s slot only after it notices disconnect
+fn reconnect(addr: SocketAddr, player_id: PlayerId) -> Result<Client, ~str> {
+    let mut attempts = 0;
+    loop {
+        match Client::connect(addr, Some(player_id)) {
+            Ok(client) => return Ok(client),
+            Err(e) => {
+                attempts += 1;
+                if attempts == RECONNECT_ATTEMPTS {
+                    return Err(e);
+                }
+                sleep(RECONNECT_DELAY_MS);
+            },
+        }
+    }
+}
+
+// Returns the winner announced by server
+fn run_bot(
+    addr: SocketAddr,
+    ai_name: &str,
+    player_id: Option<PlayerId>,
+    leave_after: Option<MInt>
+) -> Result<Option<PlayerId>, ~str> {
+    let client = try!(Client::connect(addr, player_id));
+    let mut bot = try!(Bot::new(client, ai_name));
+    if leave_after.is_some() {
+        try!(bot.play(leave_after));
+        if !bot.client.is_game_over() {
+            let player_id = bot.client.player_id();
+            drop(bot);
+            let client = try!(reconnect(addr, player_id));
+            bot = try!(Bot::new(client, ai_name));
+        }
+    }
+    try!(bot.play(None));
+    Ok(bot.client.winner())
+}
+
+fn check_version_mismatch(addr: SocketAddr) -> Result<(), ~str> {
+    let hello = ClientHello(PROTOCOL_VERSION + 1, None);
+    let hello = client_message_to_str(&hello);
+    let mut stream = match TcpStream::connect(addr) {
+        Ok(stream) => stream,
+        Err(e) => return Err(format!("{}: {}", addr, e)),
+    };
+    let _ = stream.write_line(hello.as_slice());
+    let mut reader = BufferedReader::new(stream);
+    let line = reader.read_line().unwrap_or(~"");
+    match str_to_server_message(line.as_slice()) {
+        Ok(ServerError(_)) => Ok(()),
+        _ => Err(~"server accepted wrong protocol version"),
+    }
+}
+
+// First client leaves the game and joins it back to check
+// that its state is restored from the server
+fn selftest(max_turns: MInt) -> Result<(), ~str> {
+    let core = try!(GameSettings::new(Size2{w: 9, h: 6})
+        .players(vec!(PlayerHuman, PlayerHuman))
+        .random_units(3, 0)
+        .build());
+    let loopback = SocketAddr{ip: Ipv4Addr(127, 0, 0, 1), port: 0};
+    let server = match Server::new(core, loopback, max_turns) {
+        Ok(server) => server,
+        Err(e) => return Err(format!("can not start server: {}", e)),
+    };
+    let addr = server.addr();
+    let (server_sender, server_receiver) = channel();
+    spawn(proc() {
+        let mut server = server;
+        server_sender.send(server.run());
+    });
+    try!(check_version_mismatch(addr));
+    let (sender, receiver) = channel();
+    let bots = [("greedy", Some(2)), ("minimax", None)];
+    for &(ai_name, leave_after) in bots.iter() {
+        let sender = sender.clone();
+        spawn(proc() {
+            sender.send(run_bot(addr, ai_name, None, leave_after));
+        });
+    }
+    let winner = server_receiver.recv();
+    for _ in bots.iter() {
+        let bot_winner = try!(receiver.recv());
+        if bot_winner != winner {
+            return Err(~"clients and server disagree about the winner");
+        }
+    }
+    match winner {
+        Some(PlayerId(id)) => println!("selftest: ok, player {} won", id),
+        None => println!("selftest: ok, no winner"),
+    }
+    Ok(())
+}
+
+fn print_status(receiver: Receiver<ServerStatus>) {
+    for status in receiver.iter() {
+        match status {
+            StatusPlayerConnected(PlayerId(id)) => {
+                println!("player {} connected", id);
+            },
+            StatusPlayerDisconnected(PlayerId(id)) => {
+                println!("player {} disconnected", id);
+            },
+        }
+    }
+}
+
+fn serve(options: &Options) -> Result<(), ~str> {
+    let rules = Rules::new();
+    let scenario = match Scenario::from_config(
+        options.scenario_path.as_slice(), &rules)
+    {
+        Ok(scenario) => scenario,
+        Err(e) => return Err(e.to_str()),
+    };
+    let core = try!(GameSettings::from_scenario(&scenario)
+        .rules(rules)
+        .build());
+    let addr = options.listen_addr;
+    let mut server = match Server::new(core, addr, options.max_turns) {
+        Ok(server) => server,
+        Err(e) => return Err(format!("{}: {}", addr, e)),
+    };
+    let (status_sender, status_receiver) = channel();
+    server.set_status(status_sender);
+    spawn(proc() print_status(status_receiver));
+    println!("listening on {}", server.addr());
+    match server.run() {
+        Some(PlayerId(id)) => println!("result: player {} won", id),
+        None => println!("result: no winner"),
+    }
+    Ok(())
+}
+
+fn run(options: &Options) -> Result<(), ~str> {
+    if options.is_selftest {
+        return selftest(options.max_turns);
+    }
+    match options.connect_addr {
+        Some(addr) => {
+            let ai_name = options.ai_name.as_slice();
+            match try!(run_bot(addr, ai_name, options.player_id, None)) {
+                Some(PlayerId(id)) => println!("result: player {} won", id),
+                None => println!("result: no winner"),
+            }
+            Ok(())
+        },
+        None => serve(options),
+    }
+}
+
+fn main() {
+    let args = os::args();
+    let result = match parse_options(args.as_slice()) {
+        Ok(Some(options)) => run(&options),
+        Ok(None) => Ok(()),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => {},
+        Err(e) => {
+            println!("Error: {}", e);
+            os::set_exit_status(1);
+        },
+    }
+}
+
+#[start]
+fn start(argc: int, argv: **u8) -> int {
+    native::start(argc, argv, main)
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
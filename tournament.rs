@@ -0,0 +1,347 @@
+// See LICENSE file for copyright and license details.
+
+#![deny(non_camel_case_types)]
+#![deny(non_uppercase_statics)]
+#![deny(unnecessary_qualification)]
+#![deny(unnecessary_typecast)]
+
+extern crate native;
+extern crate serialize;
+extern crate collections;
+extern crate getopts;
+extern crate core;
+
+use std::os;
+use std::io::fs::mkdir_recursive;
+use std::io::UserRWX;
+use serialize::json;
+use collections::treemap::TreeMap;
+use getopts::{optopt, optflag, getopts, usage, OptGroup};
+use core::core::{PlayerKind, PlayerAi};
+use core::types::{MBool, MInt, PlayerId, Size2};
+use core::scenario::Scenario;
+use core::rules::Rules;
+use core::settings::GameSettings;
+use core::runner::Runner;
+use core::replay::write_replay;
+use core::ai::AI_NAMES;
+
+struct Options {
+    ais: Vec<~str>,
+    games: MInt,
+    seed: u32,
+    scenario_path: Option<~str>,
+    map_size: Size2<MInt>,
+    units_per_player: MInt,
+    max_turns: MInt,
+    replays_dir: Option<Path>,
+    is_json: MBool,
+}
+
+struct GameRecord {
+    game: MInt,
+    seed: u32,
+    ais: Vec<~str>, // strategy of every player
+    winner: Option<PlayerId>,
+    turns: MInt,
+    units_lost: Vec<MInt>,
+}
+
+impl GameRecord {
+    fn winner_ai<'a>(&'a self) -> Option<&'a str> {
+        match self.winner {
+            Some(PlayerId(id)) => Some(self.ais.get(id as uint).as_slice()),
+            None => None,
+        }
+    }
+}
+
+fn get_opts() -> Vec<OptGroup> {
+    vec!(
+        optopt("a", "ai", "comma separated AI strategies", "greedy,minimax"),
+        optopt("n", "games", "number of games, default is 10", "N"),
+        optopt("s", "seed", "seed of the first game, default is 0", "SEED"),
+        optopt("m", "map", "play fixed scenario instead of generated",
+            "FILE"),
+        optopt("", "size", "size of generated map, default is 9x6", "WxH"),
+        optopt("u", "units", "units per player, default is 3", "N"),
+        optopt("t", "max-turns", "turns before draw, default is 100", "N"),
+        optopt("r", "replays", "directory to write replays to", "DIR"),
+        optflag("j", "json", "print results as JSON instead of CSV"),
+        optflag("h", "help", "print this help"),
+    )
+}
+
+fn parse_num<T: FromStr>(
+    name: &str,
+    value: Option<~str>,
+    default: T
+) -> Result<T, ~str> {
+    match value {
+        Some(value) => match from_str(value.as_slice()) {
+            Some(n) => Ok(n),
+            None => Err(format!("bad {}: '{}'", name, value)),
+        },
+        None => Ok(default),
+    }
+}
+
+fn parse_size(value: Option<~str>) -> Result<Size2<MInt>, ~str> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(Size2{w: 9, h: 6}),
+    };
+    let mut parts = value.split('x');
+    let w = parts.next().and_then(|s| from_str(s));
+    let h = parts.next().and_then(|s| from_str(s));
+    match (w, h, parts.next()) {
+        (Some(w), Some(h), None) if w > 0 && h > 0 => Ok(Size2{w: w, h: h}),
+        _ => Err(format!("bad map size: '{}'", value)),
+    }
+}
+
+fn parse_ais(value: Option<~str>) -> Result<Vec<~str>, ~str> {
+    let value = value.unwrap_or(~"greedy,minimax");
+    let ais: Vec<~str> = value.split(',').map(|s| s.to_owned()).collect();
+    if ais.len() < 2 {
+        return Err(~"at least two AI strategies are required");
+    }
+    for name in ais.iter() {
+        if !AI_NAMES.iter().any(|ai_name| *ai_name == name.as_slice()) {
+            return Err(format!("unknown AI: '{}', expected one of: {}",
+                *name, AI_NAMES.connect(", ")));
+        }
+    }
+    Ok(ais)
+}
+
+fn parse_options(args: &[~str]) -> Result<Option<Options>, ~str> {
+    let opts = get_opts();
+    let matches = match getopts(args.tail(), opts.as_slice()) {
+        Ok(matches) => matches,
+        Err(f) => return Err(f.to_err_msg()),
+    };
+    if matches.opt_present("h") {
+        let brief = format!("Usage: {} [options]", args[0]);
+        println!("{}", usage(brief.as_slice(), opts.as_slice()));
+        return Ok(None);
+    }
+    Ok(Some(Options {
+        ais: try!(parse_ais(matches.opt_str("a"))),
+        games: try!(parse_num("games count", matches.opt_str("n"), 10)),
+        seed: try!(parse_num("seed", matches.opt_str("s"), 0)),
+        scenario_path: matches.opt_str("m"),
+        map_size: try!(parse_size(matches.opt_str("size"))),
+        units_per_player: try!(parse_num(
+            "units count", matches.opt_str("u"), 3)),
+        max_turns: try!(parse_num("turns limit", matches.opt_str("t"), 100)),
+        replays_dir: matches.opt_str("r").map(|dir| Path::new(dir)),
+        is_json: matches.opt_present("j"),
+    }))
+}
+
+fn get_settings(
+    options: &Options,
+    ais: &Vec<~str>,
+    seed: u32
+) -> Result<GameSettings, ~str> {
+    let players: Vec<PlayerKind> = ais.iter()
+        .map(|name| PlayerAi(name.clone())).collect();
+    match options.scenario_path {
+        Some(ref path) => {
+            let rules = Rules::new();
+            let scenario = match Scenario::from_config(
+                path.as_slice(), &rules)
+            {
+                Ok(scenario) => scenario,
+                Err(e) => return Err(e.to_str()),
+            };
+            if scenario.players.len() != ais.len() {
+                return Err(format!("{} has {} players, but {} AIs given",
+                    *path, scenario.players.len(), ais.len()));
+            }
+            Ok(GameSettings::from_scenario(&scenario)
+                .players(players)
+                .rules(rules))
+        },
+        None => {
+            Ok(GameSettings::new(options.map_size)
+                .players(players)
+                .random_units(options.units_per_player, seed))
+        },
+    }
+}
+
+fn replay_header(record: &GameRecord) -> Vec<~str> {
+    let mut header = vec!(
+        format!("game: {}", record.game),
+        format!("seed: {}", record.seed),
+        format!("players: {}", record.ais.as_slice().connect(", ")),
+    );
+    header.push(match record.winner {
+        Some(PlayerId(id)) => format!("winner: player {}", id),
+        None => ~"winner: none",
+    });
+    header
+}
+
+// Strategies are rotated so that every one of them plays for every side
+fn play_game(options: &Options, game: MInt) -> Result<GameRecord, ~str> {
+    let seed = options.seed + game as u32;
+    let count = options.ais.len();
+    let ais: Vec<~str> = range(0, count)
+        .map(|i| options.ais.get((i + game as uint) % count).clone())
+        .collect();
+    let settings = try!(get_settings(options, &ais, seed));
+    let mut runner = Runner::new(try!(settings.build()));
+    runner.play_ai_turns(options.max_turns);
+    let record = GameRecord {
+        game: game,
+        seed: seed,
+        units_lost: range(0, count)
+            .map(|i| runner.units_lost(PlayerId(i as MInt)))
+            .collect(),
+        ais: ais,
+        winner: runner.winner(),
+        turns: runner.turns(),
+    };
+    match options.replays_dir {
+        Some(ref dir) => {
+            let path = dir.join(format!("game_{}.txt", game));
+            let header = replay_header(&record);
+            match write_replay(&path, header.as_slice(), runner.log()) {
+                Ok(()) => {},
+                Err(e) => return Err(format!("{}: {}", path.display(), e)),
+            }
+        },
+        None => {},
+    }
+    Ok(record)
+}
+
+// Every strategy is listed once, in order of first appearance
+fn unique_ais(options: &Options) -> Vec<~str> {
+    let mut ais: Vec<~str> = Vec::new();
+    for name in options.ais.iter() {
+        if !ais.contains(name) {
+            ais.push(name.clone());
+        }
+    }
+    ais
+}
+
+fn count_wins(records: &[GameRecord], ai: &str) -> MInt {
+    records.iter().filter(|r| r.winner_ai() == Some(ai)).count() as MInt
+}
+
+fn count_draws(records: &[GameRecord]) -> MInt {
+    records.iter().filter(|r| r.winner.is_none()).count() as MInt
+}
+
+fn print_csv(options: &Options, records: &[GameRecord]) {
+    let count = options.ais.len();
+    let mut header = vec!(~"game", ~"seed");
+    for i in range(0, count) {
+        header.push(format!("ai_{}", i));
+    }
+    header.push_all_move(vec!(~"winner", ~"winner_ai", ~"turns"));
+    for i in range(0, count) {
+        header.push(format!("units_lost_{}", i));
+    }
+    println!("{}", header.as_slice().connect(","));
+    for record in records.iter() {
+        let mut row = vec!(record.game.to_str(), record.seed.to_str());
+        row.push_all(record.ais.as_slice());
+        row.push(match record.winner {
+            Some(PlayerId(id)) => id.to_str(),
+            None => ~"none",
+        });
+        row.push(record.winner_ai().unwrap_or("draw").to_owned());
+        row.push(record.turns.to_str());
+        for lost in record.units_lost.iter() {
+            row.push(lost.to_str());
+        }
+        println!("{}", row.as_slice().connect(","));
+    }
+    for ai in unique_ais(options).iter() {
+        println!("# {}: {} wins", *ai, count_wins(records, ai.as_slice()));
+    }
+    println!("# draws: {}", count_draws(records));
+}
+
+fn num_to_json(n: MInt) -> json::Json {
+    json::Number(n as f64)
+}
+
+fn record_to_json(record: &GameRecord) -> json::Json {
+    let mut obj = TreeMap::new();
+    obj.insert(~"game", num_to_json(record.game));
+    obj.insert(~"seed", json::Number(record.seed as f64));
+    obj.insert(~"ais", json::List(record.ais.iter()
+        .map(|name| json::String(name.clone())).collect()));
+    obj.insert(~"winner", match record.winner {
+        Some(PlayerId(id)) => num_to_json(id),
+        None => json::Null,
+    });
+    obj.insert(~"turns", num_to_json(record.turns));
+    obj.insert(~"units_lost", json::List(record.units_lost.iter()
+        .map(|n| num_to_json(*n)).collect()));
+    json::Object(~obj)
+}
+
+fn print_json(options: &Options, records: &[GameRecord]) {
+    let mut wins = TreeMap::new();
+    for ai in unique_ais(options).iter() {
+        let count = count_wins(records, ai.as_slice());
+        wins.insert(ai.clone(), num_to_json(count));
+    }
+    let mut obj = TreeMap::new();
+    obj.insert(~"games", json::List(records.iter()
+        .map(|record| record_to_json(record)).collect()));
+    obj.insert(~"wins", json::Object(~wins));
+    obj.insert(~"draws", num_to_json(count_draws(records)));
+    println!("{}", json::Object(~obj).to_pretty_str());
+}
+
+fn run(options: &Options) -> Result<(), ~str> {
+    match options.replays_dir {
+        Some(ref dir) => match mkdir_recursive(dir, UserRWX) {
+            Ok(()) => {},
+            Err(e) => return Err(format!("{}: {}", dir.display(), e)),
+        },
+        None => {},
+    }
+    let mut records = Vec::new();
+    for game in range(0, options.games) {
+        records.push(try!(play_game(options, game)));
+    }
+    if options.is_json {
+        print_json(options, records.as_slice());
+    } else {
+        print_csv(options, records.as_slice());
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = os::args();
+    let result = match parse_options(args.as_slice()) {
+        Ok(Some(options)) => run(&options),
+        Ok(None) => Ok(()),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => {},
+        Err(e) => {
+            println!("Error: {}", e);
+            os::set_exit_status(1);
+        },
+    }
+}
+
+#[start]
+fn start(argc: int, argv: **u8) -> int {
+    native::start(argc, argv, main)
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -6,17 +6,8 @@ use cgmath::vector::{Vec3, Vector, EuclideanVector};
 use visualizer::geom::Geom;
 use core::types::{MBool, MInt, MapPos, UnitId};
 use core::game_state::GameState;
-use visualizer::types::{Scene, SceneNode, MFloat, WorldPos, NodeId};
-
-fn unit_id_to_node_id(unit_id: UnitId) -> NodeId {
-    let UnitId(id) = unit_id;
-    NodeId(id)
-}
-
-fn marker_id(unit_id: UnitId) -> NodeId {
-    let UnitId(id) = unit_id;
-    NodeId(id + 1000)
-}
+use visualizer::types::{SceneNode, MFloat, WorldPos, NodeId};
+use visualizer::scene::Scene;
 
 pub trait EventVisualizer {
     fn is_finished(&self) -> MBool;
@@ -52,11 +43,12 @@ impl EventVisualizer for EventMoveVisualizer {
     fn draw(&mut self, geom: &Geom, scene: &mut Scene, dtime: MInt) {
         let pos = self.move.step(dtime);
         {
-            let marker_node = scene.get_mut(&marker_id(self.unit_id));
+            let marker_id = scene.unit_marker_id(self.unit_id);
+            let marker_node = scene.node_mut(marker_id);
             marker_node.pos = pos.add_v(&vec3_z(geom.hex_ex_radius / 2.0));
         }
-        let node_id = unit_id_to_node_id(self.unit_id);
-        let node = scene.get_mut(&node_id);
+        let node_id = scene.unit_body_id(self.unit_id);
+        let node = scene.node_mut(node_id);
         node.pos = pos;
         if self.move.is_finished() {
             self.path.shift();
@@ -69,8 +61,8 @@ impl EventVisualizer for EventMoveVisualizer {
 
     fn end(&mut self, _: &Geom, scene: &mut Scene, _: &GameState) {
         assert!(self.path.len() == 1);
-        let node_id = unit_id_to_node_id(self.unit_id);
-        let node = scene.get_mut(&node_id);
+        let node_id = scene.unit_body_id(self.unit_id);
+        let node = scene.node_mut(node_id);
         node.pos = self.current_waypoint();
     }
 }
@@ -89,8 +81,8 @@ impl EventMoveVisualizer {
             world_path.push(world_pos);
         }
         let speed = 3.8; // TODO: Get from UnitType
-        let node_id = unit_id_to_node_id(unit_id);
-        let node = scene.get_mut(&node_id);
+        let node_id = scene.unit_body_id(unit_id);
+        let node = scene.node_mut(node_id);
         node.rot = geom.get_rot_angle(
             *world_path.get(0), *world_path.get(1));
         let move = MoveHelper::new(
@@ -147,6 +139,25 @@ impl EventVisualizer for EventEndTurnVisualizer {
     fn end(&mut self, _: &Geom, _: &mut Scene, _: &GameState) {}
 }
 
+// Move orders are shown by Visualizer itself
+pub struct EventMoveOrderVisualizer;
+
+impl EventMoveOrderVisualizer {
+    pub fn new() -> ~EventVisualizer {
+        ~EventMoveOrderVisualizer as ~EventVisualizer
+    }
+}
+
+impl EventVisualizer for EventMoveOrderVisualizer {
+    fn is_finished(&self) -> MBool {
+        true
+    }
+
+    fn draw(&mut self, _: &Geom, _: &mut Scene, _: MInt) {}
+
+    fn end(&mut self, _: &Geom, _: &mut Scene, _: &GameState) {}
+}
+
 pub struct EventCreateUnitVisualizer {
     id: UnitId,
     move: MoveHelper,
@@ -162,21 +173,21 @@ impl EventCreateUnitVisualizer {
         mesh_id: MInt,
         marker_mesh_id: MInt
     ) -> ~EventVisualizer {
-        let node_id = unit_id_to_node_id(id);
         let world_pos = unit_pos(id, pos, geom, state);
         let to = world_pos;
         let from = to.sub_v(&vec3_z(geom.hex_ex_radius / 2.0));
         let rot = rand::task_rng().gen_range::<MFloat>(0.0, 360.0);
-        scene.insert(node_id, SceneNode {
+        let body = SceneNode {
             pos: from,
             rot: rot,
             mesh_id: mesh_id,
-        });
-        scene.insert(marker_id(id), SceneNode {
+        };
+        let marker = SceneNode {
             pos: to.add_v(&vec3_z(geom.hex_ex_radius / 2.0)),
             rot: 0.0,
             mesh_id: marker_mesh_id,
-        });
+        };
+        scene.add_unit(id, body, marker);
         let move = MoveHelper::new(geom, from, to, 1.0);
         ~EventCreateUnitVisualizer {
             id: id,
@@ -191,8 +202,8 @@ impl EventVisualizer for EventCreateUnitVisualizer {
     }
 
     fn draw(&mut self, _: &Geom, scene: &mut Scene, dtime: MInt) {
-        let node_id = unit_id_to_node_id(self.id);
-        let node = scene.get_mut(&node_id);
+        let node_id = scene.unit_body_id(self.id);
+        let node = scene.node_mut(node_id);
         node.pos = self.move.step(dtime);
     }
 
@@ -261,21 +272,19 @@ impl EventAttackUnitVisualizer {
         defender_id: UnitId,
         shell_mesh_id: MInt
     ) -> ~EventVisualizer {
-        let node_id = unit_id_to_node_id(defender_id);
-        let from = scene.get(&node_id).pos;
+        let defender_node_id = scene.unit_body_id(defender_id);
+        let attacker_node_id = scene.unit_body_id(attacker_id);
+        let from = scene.node(defender_node_id).pos;
         let to = from.sub_v(&vec3_z(geom.hex_ex_radius / 2.0));
         let move = MoveHelper::new(geom, from, to, 1.0);
-        let shell_node_id = NodeId(666); // TODO
-        let shell_move = {
-            let from = scene.get(&unit_id_to_node_id(attacker_id)).pos;
-            let to = scene.get(&unit_id_to_node_id(defender_id)).pos;
-            scene.insert(shell_node_id, SceneNode {
-                pos: from,
-                rot: 0.0,
-                mesh_id: shell_mesh_id,
-            });
-            MoveHelper::new(geom, from, to, 10.0)
-        };
+        let shell_from = scene.node(attacker_node_id).pos;
+        let shell_to = scene.node(defender_node_id).pos;
+        let shell_node_id = scene.add_node(SceneNode {
+            pos: shell_from,
+            rot: 0.0,
+            mesh_id: shell_mesh_id,
+        });
+        let shell_move = MoveHelper::new(geom, shell_from, shell_to, 10.0);
         ~EventAttackUnitVisualizer {
             attacker_id: attacker_id,
             defender_id: defender_id,
@@ -292,18 +301,16 @@ impl EventVisualizer for EventAttackUnitVisualizer {
     }
 
     fn draw(&mut self, _: &Geom, scene: &mut Scene, dtime: MInt) {
-        scene.get_mut(&self.shell_node_id).pos = self.shell_move.step(dtime);
+        scene.node_mut(self.shell_node_id).pos = self.shell_move.step(dtime);
         if self.shell_move.is_finished() {
-            let node_id = unit_id_to_node_id(self.defender_id);
-            scene.get_mut(&node_id).pos = self.move.step(dtime);
+            let node_id = scene.unit_body_id(self.defender_id);
+            scene.node_mut(node_id).pos = self.move.step(dtime);
         }
     }
 
     fn end(&mut self, _: &Geom, scene: &mut Scene, _: &GameState) {
-        let node_id = unit_id_to_node_id(self.defender_id);
-        scene.remove(&node_id);
-        scene.remove(&self.shell_node_id);
-        scene.remove(&marker_id(self.defender_id));
+        scene.remove_unit(self.defender_id);
+        scene.remove_node(self.shell_node_id);
     }
 }
 
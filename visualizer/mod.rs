@@ -4,6 +4,7 @@ pub mod visualizer;
 pub mod gl_helpers;
 pub mod camera;
 pub mod types;
+pub mod scene;
 pub mod geom;
 pub mod picker;
 pub mod obj;
@@ -11,5 +12,6 @@ pub mod mesh;
 pub mod event_visualizer;
 pub mod shader;
 pub mod texture;
+pub mod text;
 
 // vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
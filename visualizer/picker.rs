@@ -11,13 +11,26 @@ use visualizer::gl_helpers::{
 use visualizer::camera::Camera;
 use visualizer::geom::Geom;
 use visualizer::mesh::Mesh;
-use visualizer::types::{Color3, MFloat, MatId, Scene, NodeId, WorldPos};
+use visualizer::types::{Color3, MFloat, MatId, WorldPos};
+use visualizer::scene::Scene;
 use visualizer::shader::Shader;
 
+// Tile is drawn with r = x and g = y, so both must fit in one byte
+static MAX_MAP_SIDE: MInt = 256;
+
 fn i_to_f(n: MInt) -> f32 {
     n as MFloat / 255.0
 }
 
+// Called before the window is opened, see TilePicker::new
+pub fn check_map_size(map_size: Size2<MInt>) -> Result<(), ~str> {
+    if map_size.w > MAX_MAP_SIDE || map_size.h > MAX_MAP_SIDE {
+        return Err(format!("map {}x{} is too big, tile picker supports \
+            up to {}x{}", map_size.w, map_size.h, MAX_MAP_SIDE, MAX_MAP_SIDE));
+    }
+    Ok(())
+}
+
 fn get_mesh(geom: &Geom, map_size: Size2<MInt>, shader: &Shader) -> Mesh {
     use std::slice::Vector;
     let mut c_data = Vec::new();
@@ -62,9 +75,12 @@ impl TilePicker {
     pub fn new(
         win_size: Size2<MInt>,
         geom: &Geom,
-        map_size: Size2<MInt>
+        map_size: Size2<MInt>,
+        vs_path: &str,
+        fs_path: &str
     ) -> ~TilePicker {
-        let shader = Shader::new("pick.vs.glsl", "pick.fs.glsl");
+        assert!(check_map_size(map_size).is_ok());
+        let shader = Shader::new(vs_path, fs_path);
         let mvp_mat_id = MatId(shader.get_uniform("mvp_mat"));
         let map_mesh = get_mesh(geom, map_size, &shader);
         let tile_picker = ~TilePicker {
@@ -87,15 +103,17 @@ impl TilePicker {
             let scale_factor = 0.5;
             geom.index_to_hex_vertex(n).mul_s(scale_factor)
         }
-        let last_unit_node_id = 1000; // TODO
         let mut c_data = Vec::new();
         let mut v_data = Vec::new();
-        for (node_id, node) in scene.iter() {
-            let NodeId(id) = *node_id;
-            if id >= last_unit_node_id {
-                continue;
-            }
-            let color = Color3 {r: i_to_f(id), g: 0.0, b: i_to_f(2)};
+        let bodies = scene.unit_bodies();
+        for &(unit_id, node) in bodies.iter() {
+            // Unit id is split between red and green bytes
+            let UnitId(id) = unit_id;
+            let color = Color3 {
+                r: i_to_f(id % 256),
+                g: i_to_f(id / 256),
+                b: i_to_f(2),
+            };
             for num in range(0 as MInt, 6) {
                 v_data.push(node.pos + get_hex_vertex(geom, num));
                 c_data.push(color);
@@ -132,7 +150,7 @@ impl TilePicker {
         match b {
             0 => PickedNothing,
             1 => PickedMapPos(Vec2{x: r, y: g}),
-            2 => PickedUnitId(UnitId(r)),
+            2 => PickedUnitId(UnitId(r + g * 256)),
             _ => fail!(),
         }
     }
@@ -0,0 +1,93 @@
+// See LICENSE file for copyright and license details.
+
+use collections::hashmap::HashMap;
+use core::types::{MInt, UnitId};
+use visualizer::types::{SceneNode, NodeId};
+
+// Every unit is drawn with two nodes:
+// its body and a marker of its owner above it
+struct UnitNodes {
+    body_id: NodeId,
+    marker_id: NodeId,
+}
+
+pub struct Scene {
+    nodes: HashMap<NodeId, SceneNode>,
+    unit_nodes: HashMap<UnitId, UnitNodes>,
+    next_node_id: MInt, // ids of removed nodes are never reused
+}
+
+impl<'a> Scene {
+    pub fn new() -> Scene {
+        Scene {
+            nodes: HashMap::new(),
+            unit_nodes: HashMap::new(),
+            next_node_id: 0,
+        }
+    }
+
+    pub fn nodes(&'a self) -> &'a HashMap<NodeId, SceneNode> {
+        &self.nodes
+    }
+
+    pub fn node(&'a self, node_id: NodeId) -> &'a SceneNode {
+        self.nodes.get(&node_id)
+    }
+
+    pub fn node_mut(&'a mut self, node_id: NodeId) -> &'a mut SceneNode {
+        self.nodes.get_mut(&node_id)
+    }
+
+    pub fn add_node(&mut self, node: SceneNode) -> NodeId {
+        let node_id = NodeId(self.next_node_id);
+        self.next_node_id += 1;
+        self.nodes.insert(node_id, node);
+        node_id
+    }
+
+    pub fn remove_node(&mut self, node_id: NodeId) {
+        self.nodes.remove(&node_id);
+    }
+
+    pub fn add_unit(
+        &mut self,
+        unit_id: UnitId,
+        body: SceneNode,
+        marker: SceneNode
+    ) {
+        assert!(self.unit_nodes.find(&unit_id).is_none());
+        let body_id = self.add_node(body);
+        let marker_id = self.add_node(marker);
+        self.unit_nodes.insert(unit_id, UnitNodes {
+            body_id: body_id,
+            marker_id: marker_id,
+        });
+    }
+
+    pub fn remove_unit(&mut self, unit_id: UnitId) {
+        let (body_id, marker_id) = {
+            let unit_nodes = self.unit_nodes.get(&unit_id);
+            (unit_nodes.body_id, unit_nodes.marker_id)
+        };
+        self.remove_node(body_id);
+        self.remove_node(marker_id);
+        self.unit_nodes.remove(&unit_id);
+    }
+
+    pub fn unit_body_id(&self, unit_id: UnitId) -> NodeId {
+        self.unit_nodes.get(&unit_id).body_id
+    }
+
+    pub fn unit_marker_id(&self, unit_id: UnitId) -> NodeId {
+        self.unit_nodes.get(&unit_id).marker_id
+    }
+
+    // Bodies of all units, for TilePicker
+    pub fn unit_bodies(&'a self) -> Vec<(UnitId, &'a SceneNode)> {
+        self.unit_nodes.iter().map(|(unit_id, unit_nodes)| {
+            (*unit_id, self.nodes.get(&unit_nodes.body_id))
+        }).collect()
+    }
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
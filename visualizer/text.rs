@@ -0,0 +1,135 @@
+// See LICENSE file for copyright and license details.
+
+use cgmath::projection;
+use cgmath::matrix::Mat4;
+use cgmath::vector::Vec3;
+use core::types::{MInt, Size2};
+use visualizer::mesh::Mesh;
+use visualizer::types::{Color3, MFloat, VertexCoord};
+use visualizer::shader::Shader;
+
+// Built-in 5x7 bitmap font, so plain text needs no font files.
+// Letters are drawn in upper case.
+
+static GLYPH_W: MInt = 5;
+static GLYPH_H: MInt = 7;
+static ADVANCE_X: MInt = GLYPH_W + 1;
+static ADVANCE_Y: MInt = GLYPH_H + 3;
+static PIXEL_SIZE: MFloat = 3.0; // screen pixels per glyph pixel
+static MARGIN: MFloat = 20.0;
+
+// Rows from top to bottom, the highest of 5 bits is the left pixel
+fn glyph(c: char) -> [u8, ..7] {
+    match c.to_uppercase() {
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        ' ' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '\'' => [0b01100, 0b00100, 0b01000, 0, 0, 0, 0],
+        // Unknown characters are shown as '?'
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+    }
+}
+
+fn push_pixel(
+    v_data: &mut Vec<VertexCoord>,
+    c_data: &mut Vec<Color3>,
+    x: MInt,
+    y: MInt,
+    color: Color3
+) {
+    let x0 = MARGIN + x as MFloat * PIXEL_SIZE;
+    let y0 = MARGIN + y as MFloat * PIXEL_SIZE;
+    let x1 = x0 + PIXEL_SIZE;
+    let y1 = y0 + PIXEL_SIZE;
+    let corners = [
+        Vec3{x: x0, y: y0, z: 0.0},
+        Vec3{x: x1, y: y0, z: 0.0},
+        Vec3{x: x1, y: y1, z: 0.0},
+        Vec3{x: x0, y: y0, z: 0.0},
+        Vec3{x: x1, y: y1, z: 0.0},
+        Vec3{x: x0, y: y1, z: 0.0},
+    ];
+    for corner in corners.iter() {
+        v_data.push(*corner);
+        c_data.push(color);
+    }
+}
+
+// Lines of text at the top left corner of the window, coordinates
+// are window pixels (see `text_mat`). `shader` must take vertex
+// colors, like the picker`s one.
+pub fn get_text_mesh(
+    shader: &Shader,
+    lines: &[~str],
+    color: Color3
+) -> Mesh {
+    let mut v_data = Vec::new();
+    let mut c_data = Vec::new();
+    for (line_index, line) in lines.iter().enumerate() {
+        let top = line_index as MInt * ADVANCE_Y;
+        for (char_index, c) in line.chars().enumerate() {
+            let left = char_index as MInt * ADVANCE_X;
+            let rows = glyph(c);
+            for y in range(0, GLYPH_H) {
+                for x in range(0, GLYPH_W) {
+                    let shift = (GLYPH_W - 1 - x) as u8;
+                    if (rows[y as uint] >> shift) & 1 != 0 {
+                        push_pixel(&mut v_data, &mut c_data,
+                            left + x, top + y, color);
+                    }
+                }
+            }
+        }
+    }
+    let mut mesh = Mesh::new(v_data.as_slice());
+    mesh.set_color(c_data.as_slice());
+    mesh.prepare(shader);
+    mesh
+}
+
+// Maps window pixels to the screen, y goes down
+pub fn text_mat(win_size: Size2<MInt>) -> Mat4<MFloat> {
+    let w = win_size.w as MFloat;
+    let h = win_size.h as MFloat;
+    projection::ortho(0.0, w, h, 0.0, -1.0, 1.0)
+}
+
+// vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
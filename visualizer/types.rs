@@ -1,6 +1,5 @@
 // See LICENSE file for copyright and license details.
 
-use collections::hashmap::HashMap;
 use gl::types::{GLfloat, GLuint};
 use cgmath::vector::{Vec3, Vec2};
 use core::types::MInt;
@@ -29,6 +28,4 @@ pub struct SceneNode {
 #[deriving(Ord, Eq, TotalEq, Hash)]
 pub struct NodeId(pub MInt);
 
-pub type Scene = HashMap<NodeId, SceneNode>;
-
 // vim: set tabstop=4 shiftwidth=4 softtabstop=4 expandtab:
@@ -9,8 +9,13 @@ use core::map::MapPosIter;
 use core::types::{Size2, MInt, MBool, UnitId, PlayerId, MapPos, Point2};
 use core::game_state::GameState;
 use core::pathfinder::Pathfinder;
-use core::conf::Config;
+use core::conf::{Config, ConfigError};
+use core::locator::Locator;
 use core::core;
+use core::core::CoreInterface;
+use core::ai::{Ai, get_ais};
+use core::rules::Rules;
+use core::summary::TurnSummary;
 use visualizer::gl_helpers::{
     set_clear_color,
     clear_screen,
@@ -25,8 +30,9 @@ use visualizer::geom::Geom;
 use visualizer::picker;
 use visualizer::obj;
 use visualizer::mesh::Mesh;
+use visualizer::scene::Scene;
 use visualizer::types::{
-    Scene,
+    Color3,
     VertexCoord,
     TextureCoord,
     MFloat,
@@ -39,9 +45,11 @@ use visualizer::event_visualizer::{
     EventEndTurnVisualizer,
     EventCreateUnitVisualizer,
     EventAttackUnitVisualizer,
+    EventMoveOrderVisualizer,
 };
 use visualizer::shader::Shader;
 use visualizer::texture::Texture;
+use visualizer::text::{get_text_mesh, text_mat};
 
 fn build_hex_mesh(&geom: &Geom, map_size: Size2<MInt>) -> Vec<VertexCoord> {
     let mut vertex_data = Vec::new();
@@ -95,15 +103,18 @@ fn get_marker(shader: &Shader, tex_path: ~str) -> Mesh {
 fn get_scenes(players_count: MInt) -> HashMap<PlayerId, Scene> {
     let mut m = HashMap::new();
     for i in range(0, players_count) {
-        m.insert(PlayerId(i), HashMap::new());
+        m.insert(PlayerId(i), Scene::new());
     }
     m
 }
 
-fn get_game_states(players_count: MInt) -> HashMap<PlayerId, GameState> {
+fn get_game_states(
+    players_count: MInt,
+    rules: &Rules
+) -> HashMap<PlayerId, GameState> {
     let mut m = HashMap::new();
     for i in range(0, players_count) {
-        m.insert(PlayerId(i), GameState::new());
+        m.insert(PlayerId(i), GameState::new(rules.clone()));
     }
     m
 }
@@ -119,17 +130,43 @@ fn get_pathfinders(
     m
 }
 
-fn get_map_mesh(geom: &Geom, map_size: Size2<MInt>, shader: &Shader) -> Mesh {
-    let tex = Texture::new(~"data/floor.png");
+fn get_event_lists(
+    players_count: MInt,
+) -> HashMap<PlayerId, Vec<core::Event>> {
+    let mut m = HashMap::new();
+    for i in range(0, players_count) {
+        m.insert(PlayerId(i), Vec::new());
+    }
+    m
+}
+
+// Visualizer`s config with defaults of optional fields
+pub fn load_config(
+    path: &str,
+    overrides: &[~str]
+) -> Result<Config, ConfigError> {
+    let mut config = Config::new();
+    config.set_default("screen_size", "{\"w\": 640, \"h\": 480}");
+    try!(config.add_layers(path, overrides));
+    Ok(config)
+}
+
+fn get_map_mesh(
+    geom: &Geom,
+    map_size: Size2<MInt>,
+    shader: &Shader,
+    tex_path: ~str
+) -> Mesh {
+    let tex = Texture::new(tex_path);
     let mut mesh = Mesh::new(build_hex_mesh(geom, map_size).as_slice());
     mesh.set_texture(tex, build_hex_tex_coord(map_size).as_slice());
     mesh.prepare(shader);
     mesh
 }
 
-fn load_unit_mesh(shader: &Shader) -> Mesh {
-    let tex = Texture::new(~"data/tank.png");
-    let obj = obj::Model::new("data/tank.obj");
+fn load_unit_mesh(shader: &Shader, tex_path: ~str, obj_path: &str) -> Mesh {
+    let tex = Texture::new(tex_path);
+    let obj = obj::Model::new(obj_path);
     let mut mesh = Mesh::new(obj.build().as_slice());
     mesh.set_texture(tex, obj.build_tex_coord().as_slice());
     mesh.prepare(shader);
@@ -159,23 +196,51 @@ pub struct Visualizer<'a> {
     unit_under_cursor_id: Option<UnitId>,
     geom: Geom,
     scenes: HashMap<PlayerId, Scene>,
-    core: ~core::Core,
+    core: ~CoreInterface,
     event: Option<core::Event>,
     event_visualizer: Option<~EventVisualizer>,
     game_state: HashMap<PlayerId, GameState>,
     pathfinders: HashMap<PlayerId, Pathfinder>,
+    ais: HashMap<PlayerId, ~Ai>,
+    ai_commands: Vec<core::Command>,
+    ai_events: HashMap<PlayerId, Vec<core::Event>>,
     last_time: Time,
     dtime: MInt,
     win_size: Size2<MInt>,
     glfw: glfw::Glfw,
     events: Receiver<(f64, glfw::WindowEvent)>,
+    is_hot_seat: MBool,
+    last_human_id: Option<PlayerId>,
+    pending_events: Vec<core::Event>,
+    text_shader: Shader,
+    text_mat_id: MatId,
+    handover_text: Option<Mesh>, // turn summary, Some while map is hidden
 }
 
 impl<'a> Visualizer<'a> {
-    pub fn new() -> ~Visualizer {
-        let players_count = 2;
-        let config = Config::new("conf_visualizer.json");
-        let win_size = config.get::<Size2<MInt>>("screen_size");
+    pub fn new(
+        core: ~CoreInterface,
+        is_hot_seat: MBool,
+        config: &Config,
+        locator: &Locator
+    ) -> Result<~Visualizer, ~str> {
+        let win_size: Size2<MInt> = match config.get("screen_size") {
+            Ok(win_size) => win_size,
+            Err(e) => return Err(e.to_str()),
+        };
+        // Missing file or too big map is reported before the window
+        // is opened
+        try!(picker::check_map_size(core.map_size()));
+        let pick_vs_path = try!(locator.find_str("pick.vs.glsl"));
+        let pick_fs_path = try!(locator.find_str("pick.fs.glsl"));
+        let vs_path = try!(locator.find_str("normal.vs.glsl"));
+        let fs_path = try!(locator.find_str("normal.fs.glsl"));
+        let floor_path = try!(locator.find_str("floor.png"));
+        let tank_tex_path = try!(locator.find_str("tank.png"));
+        let tank_obj_path = try!(locator.find_str("tank.obj"));
+        let shell_path = try!(locator.find_str("shell.png"));
+        let flag_1_path = try!(locator.find_str("flag1.png"));
+        let flag_2_path = try!(locator.find_str("flag2.png"));
         let glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
         let (win, events) = glfw.create_window(
             win_size.w as u32,
@@ -188,22 +253,28 @@ impl<'a> Visualizer<'a> {
         load_gl_funcs_with(|procname| glfw.get_proc_address(procname));
         init_opengl();
         let geom = Geom::new();
-        let core = core::Core::new();
+        let players_count = core.players_count();
         let map_size = core.map_size();
-        let picker = picker::TilePicker::new(
-            win_size, &geom, core.map_size());
-        let shader = Shader::new("normal.vs.glsl", "normal.fs.glsl");
+        let rules = core.rules();
+        let ais = get_ais(&*core);
+        let picker = picker::TilePicker::new(win_size, &geom, core.map_size(),
+            pick_vs_path.as_slice(), pick_fs_path.as_slice());
+        let shader = Shader::new(vs_path.as_slice(), fs_path.as_slice());
         let mvp_mat_id = MatId(shader.get_uniform("mvp_mat"));
+        let text_shader = Shader::new(
+            pick_vs_path.as_slice(), pick_fs_path.as_slice());
+        let text_mat_id = MatId(text_shader.get_uniform("mvp_mat"));
         let mut meshes = Vec::new();
         let map_mesh_id = add_mesh(
-            &mut meshes, get_map_mesh(&geom, map_size, &shader));
-        let unit_mesh_id = add_mesh(&mut meshes, load_unit_mesh(&shader));
+            &mut meshes, get_map_mesh(&geom, map_size, &shader, floor_path));
+        let unit_mesh_id = add_mesh(&mut meshes, load_unit_mesh(
+            &shader, tank_tex_path, tank_obj_path.as_slice()));
         let shell_mesh_id = add_mesh(
-            &mut meshes, get_marker(&shader, ~"data/shell.png"));
+            &mut meshes, get_marker(&shader, shell_path));
         let marker_1_mesh_id = add_mesh(
-            &mut meshes, get_marker(&shader, ~"data/flag1.png"));
+            &mut meshes, get_marker(&shader, flag_1_path));
         let marker_2_mesh_id = add_mesh(
-            &mut meshes, get_marker(&shader, ~"data/flag2.png"));
+            &mut meshes, get_marker(&shader, flag_2_path));
         let vis = ~Visualizer {
             map_mesh_id: map_mesh_id,
             unit_mesh_id: unit_mesh_id,
@@ -225,15 +296,24 @@ impl<'a> Visualizer<'a> {
             event_visualizer: None,
             event: None,
             scenes: get_scenes(players_count),
-            game_state: get_game_states(players_count),
+            game_state: get_game_states(players_count, &rules),
             pathfinders: get_pathfinders(players_count, map_size),
+            ais: ais,
+            ai_commands: Vec::new(),
+            ai_events: get_event_lists(players_count),
             last_time: precise_time_ns(),
             dtime: 0,
             win_size: win_size,
             glfw: glfw,
             events: events,
+            is_hot_seat: is_hot_seat,
+            last_human_id: None,
+            pending_events: Vec::new(),
+            text_shader: text_shader,
+            text_mat_id: text_mat_id,
+            handover_text: None,
         };
-        vis
+        Ok(vis)
     }
 
     fn win<'a>(&'a self) -> &'a glfw::Window {
@@ -245,7 +325,7 @@ impl<'a> Visualizer<'a> {
     }
 
     fn draw_units(&self) {
-        for (_, node) in self.scene().iter() {
+        for (_, node) in self.scene().nodes().iter() {
             let mut m = tr(self.camera.mat(), node.pos);
             m = rot_z(m, node.rot);
             self.shader.uniform_mat4f(self.mvp_mat_id, &m);
@@ -253,16 +333,55 @@ impl<'a> Visualizer<'a> {
         }
     }
 
+    // Shows where selected unit stops at the end of every turn
+    fn draw_move_order(&self) {
+        let unit_id = match self.selected_unit_id {
+            Some(unit_id) => unit_id,
+            None => return,
+        };
+        let player_id = self.core.player_id();
+        let state = self.game_state.get(&player_id);
+        let unit = state.units.find(&unit_id);
+        let path = state.move_orders.find(&unit_id);
+        let (unit, path) = match (unit, path) {
+            (Some(unit), Some(path)) => (unit, path),
+            _ => return,
+        };
+        let pf = self.pathfinders.get(&player_id);
+        let segments = pf.split_path(
+            state, unit, path.as_slice(), state.rules.move_distance);
+        for segment in segments.iter() {
+            let pos = *segment.last().unwrap();
+            let world_pos = self.geom.map_pos_to_world_pos(pos);
+            let m = tr(self.camera.mat(), world_pos);
+            self.shader.uniform_mat4f(self.mvp_mat_id, &m);
+            self.meshes.get(self.shell_mesh_id as uint).draw(&self.shader);
+        }
+    }
+
     fn draw_map(&self) {
         self.shader.uniform_mat4f(self.mvp_mat_id, &self.camera.mat());
         self.meshes.get(self.map_mesh_id as uint).draw(&self.shader);
     }
 
+    fn draw_handover_text(&self) {
+        self.text_shader.activate();
+        let m = text_mat(self.win_size);
+        self.text_shader.uniform_mat4f(self.text_mat_id, &m);
+        self.handover_text.get_ref().draw(&self.text_shader);
+    }
+
     fn draw(&mut self) {
         set_clear_color(0.3, 0.3, 0.3);
         clear_screen();
+        if self.handover_text.is_some() {
+            self.draw_handover_text();
+            self.win().swap_buffers();
+            return;
+        }
         self.shader.activate();
         self.draw_units();
+        self.draw_move_order();
         self.draw_map();
         if !self.event_visualizer.is_none() {
             let scene = self.scenes.get_mut(&self.core.player_id());
@@ -276,6 +395,30 @@ impl<'a> Visualizer<'a> {
         return !self.win().should_close()
     }
 
+    fn is_ai_turn(&self) -> MBool {
+        self.ais.contains_key(&self.core.player_id())
+    }
+
+    fn is_input_blocked(&self) -> MBool {
+        self.handover_text.is_some()
+            || self.event_visualizer.is_some()
+            || self.is_ai_turn()
+            || !self.core.is_player_turn()
+    }
+
+    fn do_ai_command(&mut self) {
+        let player_id = self.core.player_id();
+        if self.ai_commands.len() == 0 {
+            let state = self.game_state.get(&player_id);
+            let events = self.ai_events.get_mut(&player_id);
+            let ai = self.ais.get_mut(&player_id);
+            self.ai_commands = ai.take_turn(state, events.as_slice());
+            events.clear();
+        }
+        let cmd = self.ai_commands.shift().unwrap();
+        self.core.do_command(cmd);
+    }
+
     fn end_turn(&mut self) {
         self.core.do_command(core::CommandEndTurn);
         self.selected_unit_id = None;
@@ -283,8 +426,7 @@ impl<'a> Visualizer<'a> {
 
     fn is_full_tile(&self, pos: MapPos) -> MBool {
         let state = self.game_state.get(&self.core.player_id());
-        let max_units_per_tile = 6;
-        state.units_at(pos).len() >= max_units_per_tile
+        state.is_full_tile(pos)
     }
 
     fn create_unit(&mut self) {
@@ -314,17 +456,58 @@ impl<'a> Visualizer<'a> {
         if self.unit_under_cursor_id.is_some() {
             let unit_id = self.unit_under_cursor_id.unwrap();
             self.selected_unit_id = Some(unit_id);
-            let state = self.game_state.get(&self.core.player_id());
-            let pf = self.pathfinders.get_mut(&self.core.player_id());
-            pf.fill_map(state, state.units.get(&unit_id));
         }
     }
 
+    // Hides the screen until next human player presses a key
+    // so that players sharing one screen do not see each other`s map
+    fn start_handover(&mut self) {
+        let player_id = self.core.player_id();
+        loop {
+            match self.core.get_event() {
+                Some(event) => self.pending_events.push(event),
+                None => break,
+            }
+        }
+        let summary = TurnSummary::new(
+            self.game_state.get(&player_id),
+            player_id,
+            self.pending_events.as_slice(),
+        );
+        let PlayerId(id) = player_id;
+        let title = format!("Marauder: player {}, press any key", id);
+        self.win().set_title(title.as_slice());
+        let mut lines = summary.to_lines();
+        lines.push(~"");
+        lines.push(~"press any key");
+        let white = Color3{r: 1.0, g: 1.0, b: 1.0};
+        self.handover_text = Some(get_text_mesh(
+            &self.text_shader, lines.as_slice(), white));
+        self.last_human_id = Some(player_id);
+    }
+
+    fn end_handover(&mut self) {
+        self.handover_text = None;
+        self.selected_unit_id = None;
+        self.win().set_title("Marauder");
+    }
+
+    fn is_handover_needed(&self) -> MBool {
+        self.is_hot_seat
+            && self.handover_text.is_none()
+            && !self.is_ai_turn()
+            && self.last_human_id != Some(self.core.player_id())
+    }
+
     fn handle_key_event(&mut self, key: glfw::Key) {
         match key {
             glfw::KeyEscape | glfw::KeyQ => {
                 self.win().set_should_close(true);
             },
+            _ if self.handover_text.is_some() => {
+                self.end_handover();
+                return;
+            },
             glfw::KeyUp => self.camera.move(270.0, 0.1),
             glfw::KeyDown => self.camera.move(90.0, 0.1),
             glfw::KeyRight => self.camera.move(0.0, 0.1),
@@ -333,7 +516,7 @@ impl<'a> Visualizer<'a> {
             glfw::KeyEqual => self.camera.zoom /= 1.3,
             _ => {},
         }
-        if self.event_visualizer.is_some() {
+        if self.is_input_blocked() {
             return;
         }
         match key {
@@ -371,16 +554,33 @@ impl<'a> Visualizer<'a> {
             return;
         }
         let unit_id = self.selected_unit_id.unwrap();
-        let pf = self.pathfinders.get_mut(&self.core.player_id());
-        let path = pf.get_path(pos);
-        if path.len() < 2 {
+        let (path, move_distance) = {
+            let player_id = self.core.player_id();
+            let state = self.game_state.get(&player_id);
+            let unit = match state.units.find(&unit_id) {
+                Some(unit) => unit,
+                None => return,
+            };
+            let pf = self.pathfinders.get_mut(&player_id);
+            match pf.find_path(state, unit, unit.pos, pos) {
+                Some(path) => (path, state.rules.move_distance),
+                None => return,
+            }
+        };
+        if path.positions.len() < 2 {
             return;
         }
-        self.core.do_command(core::CommandMove(unit_id, path));
+        // Too far to get there in one turn
+        let command = if path.cost > move_distance {
+            core::CommandSetMoveOrder(unit_id, path.positions)
+        } else {
+            core::CommandMove(unit_id, path.positions)
+        };
+        self.core.do_command(command);
     }
 
     fn handle_mouse_button_event(&mut self) {
-        if self.event_visualizer.is_some() {
+        if self.is_input_blocked() {
             return;
         }
         if self.map_pos_under_cursor.is_some() {
@@ -473,6 +673,9 @@ impl<'a> Visualizer<'a> {
             core::EventEndTurn(_, _) => {
                 EventEndTurnVisualizer::new()
             },
+            core::EventMoveOrder(_, _) => {
+                EventMoveOrderVisualizer::new()
+            },
             core::EventCreateUnit(id, ref pos, player_id) => {
                 let marker_mesh = match player_id {
                     PlayerId(0) => self.marker_1_mesh_id,
@@ -509,25 +712,54 @@ impl<'a> Visualizer<'a> {
     }
 
     fn end_event_visualization(&mut self) {
-        let scene = self.scenes.get_mut(&self.core.player_id());
-        let state = self.game_state.get_mut(&self.core.player_id());
+        let player_id = self.core.player_id();
+        let scene = self.scenes.get_mut(&player_id);
+        let state = self.game_state.get_mut(&player_id);
         self.event_visualizer.get_mut_ref().end(&self.geom, scene, state);
         state.apply_event(self.event.get_ref());
+        if self.ais.contains_key(&player_id) {
+            let events = self.ai_events.get_mut(&player_id);
+            events.push(self.event.get_ref().clone());
+        }
         self.event_visualizer = None;
         self.event = None;
-        if self.selected_unit_id.is_some() {
-            let unit_id = self.selected_unit_id.unwrap();
-            let pf = self.pathfinders.get_mut(&self.core.player_id());
-            pf.fill_map(state, state.units.get(&unit_id));
-        }
         self.picker.update_units(&self.geom, scene);
     }
 
+    fn get_event(&mut self) -> Option<core::Event> {
+        match self.pending_events.shift() {
+            Some(event) => Some(event),
+            None => self.core.get_event(),
+        }
+    }
+
+    fn show_core_error(&mut self) {
+        match self.core.take_error() {
+            Some(e) => {
+                let title = format!("Marauder: {}", e);
+                self.win().set_title(title.as_slice());
+            },
+            None => {},
+        }
+    }
+
     fn logic(&mut self) {
+        self.show_core_error();
+        if self.handover_text.is_some() {
+            return;
+        }
         if self.event_visualizer.is_none() {
-            match self.core.get_event() {
+            if self.is_handover_needed() {
+                self.start_handover();
+                return;
+            }
+            match self.get_event() {
                 Some(e) => self.start_event_visualization(e),
-                None => {},
+                None => {
+                    if self.is_ai_turn() {
+                        self.do_ai_command();
+                    }
+                },
             }
         } else if self.event_visualizer.get_ref().is_finished() {
             self.end_event_visualization();